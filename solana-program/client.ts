@@ -24,11 +24,19 @@ export const BONDING_CURVE_CONFIG = {
   VIRTUAL_TOKEN_RESERVES: 1_073_000_000 * 1e9,       // 1.073B tokens (with 9 decimals)
   BONDING_CURVE_SUPPLY: 800_000_000 * 1e9,           // 800M tokens for bonding curve
   CREATOR_SUPPLY: 200_000_000 * 1e9,                 // 200M tokens for creator
-  GRADUATION_THRESHOLD: 85_000 * LAMPORTS_PER_SOL,   // 85k SOL
+  GRADUATION_THRESHOLD: 85 * LAMPORTS_PER_SOL,       // 85 SOL of real SOL raised (Standard preset)
   DEFAULT_CREATOR_FEE_BPS: 200,                      // 2%
   DEFAULT_PLATFORM_FEE_BPS: 100,                     // 1%
+  PRE_GRADUATION_FEE_BPS: 200,                       // 2% total across all fee buckets
 };
 
+// Curve presets seeded into the config by initialize_config (Standard matches the values above)
+export const CURVE_PRESETS = {
+  STANDARD: 0,  // 30 SOL virtual reserves
+  LOW_CAP: 1,   // 15 SOL virtual reserves
+  HIGH_CAP: 2,  // 60 SOL virtual reserves
+} as const;
+
 export class BondingCurveClient {
   program: Program<BondingCurve>;
   provider: anchor.AnchorProvider;
@@ -49,30 +57,169 @@ export class BondingCurveClient {
   }
 
   /**
-   * Buy tokens with SOL
+   * Get the PDA address for the system-owned vault holding a curve's SOL
+   */
+  static getSolVaultPDA(mint: PublicKey, programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("sol_vault"), mint.toBuffer()],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address for the global config
+   */
+  static getConfigPDA(programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address for a fee vault ("platform", "prize_pool" or "reserves")
+   */
+  static getFeeVaultPDA(bucket: string, programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_vault"), Buffer.from(bucket)],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address for a registered referrer
+   */
+  static getReferrerPDA(wallet: PublicKey, programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("referrer"), wallet.toBuffer()],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address that signs the fee wrapper CPI in `graduate`.
+   * Register it as the fee wrapper config's `graduation_authority`.
+   */
+  static getGraduationAuthorityPDA(programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("graduation_authority")],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address for a curve's price observation ring buffer
+   */
+  static getPriceObservationsPDA(mint: PublicKey, programId: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("price_observations"), mint.toBuffer()],
+      programId
+    );
+  }
+
+  /**
+   * Get the PDA address for a wallet's trade stats, for one token or across all tokens
+   */
+  static getUserStatsPDA(user: PublicKey, programId: PublicKey, mint?: PublicKey): [PublicKey, number] {
+    const seeds = [Buffer.from("user_stats"), user.toBuffer()];
+    if (mint) seeds.push(mint.toBuffer());
+    return PublicKey.findProgramAddressSync(seeds, programId);
+  }
+
+  /**
+   * Time-weighted average price in SOL per token between two accumulator observations.
+   * Observations are `{ slot, priceCumulative }` pairs read from a curve's
+   * `lastUpdateSlot`/`priceCumulative` or from its price observation buffer.
+   */
+  static twap(
+    older: { slot: anchor.BN; priceCumulative: anchor.BN },
+    newer: { slot: anchor.BN; priceCumulative: anchor.BN }
+  ): number {
+    const elapsed = newer.slot.sub(older.slot);
+    if (elapsed.lten(0)) {
+      throw new Error("Observations must be at least one slot apart");
+    }
+    // The accumulator is a wrapping u128 of Q64.64 prices in lamports per base unit
+    const delta = newer.priceCumulative.sub(older.priceCumulative).umod(new anchor.BN(1).shln(128));
+    const averageQ64 = delta.div(elapsed);
+    // SOL and the token both use 9 decimals, so lamports per base unit equals SOL per token
+    return Number(averageQ64.toString()) / 2 ** 64;
+  }
+
+  /**
+   * Move a curve's pending platform, prize pool and reserves fees into the fee vaults
+   */
+  async sweepFees(mint: PublicKey): Promise<string> {
+    const programId = this.program.programId;
+    const [bondingCurve] = BondingCurveClient.getBondingCurvePDA(mint, programId);
+
+    return await this.program.methods
+      .sweepFees()
+      .accounts({
+        bondingCurve,
+        platformVault: BondingCurveClient.getFeeVaultPDA("platform", programId)[0],
+        prizePoolVault: BondingCurveClient.getFeeVaultPDA("prize_pool", programId)[0],
+        reservesVault: BondingCurveClient.getFeeVaultPDA("reserves", programId)[0],
+        solVault: BondingCurveClient.getSolVaultPDA(mint, programId)[0],
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  }
+
+  /**
+   * Crank a graduated curve's migration. Anyone can call this and is paid the config's graduation reward
+   */
+  async graduate(
+    mint: PublicKey,
+    cranker: Keypair,
+    feeWrapperProgramId: PublicKey
+  ): Promise<string> {
+    const programId = this.program.programId;
+    const [bondingCurve] = BondingCurveClient.getBondingCurvePDA(mint, programId);
+
+    return await this.program.methods
+      .graduate()
+      .accounts({
+        config: BondingCurveClient.getConfigPDA(programId)[0],
+        bondingCurve,
+        reservesVault: BondingCurveClient.getFeeVaultPDA("reserves", programId)[0],
+        graduationAuthority: BondingCurveClient.getGraduationAuthorityPDA(programId)[0],
+        wrapperConfig: PublicKey.findProgramAddressSync(
+          [Buffer.from("wrapper_config")],
+          feeWrapperProgramId
+        )[0],
+        wrapper: PublicKey.findProgramAddressSync(
+          [Buffer.from("wrapper"), mint.toBuffer()],
+          feeWrapperProgramId
+        )[0],
+        cranker: cranker.publicKey,
+        feeWrapperProgram: feeWrapperProgramId,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([cranker])
+      .rpc();
+  }
+
+  /**
+   * Buy tokens with SOL.
+   * Pass the staking program to count the buyer's stake towards holder fee tiers.
    */
   async buyTokens(
     mint: PublicKey,
     buyer: Keypair,
     solAmount: number,
     slippageBps: number = 500, // 5% slippage
-    platformWallet: PublicKey
+    referrerWallet?: PublicKey,
+    stakingProgramId?: PublicKey
   ): Promise<string> {
     const [bondingCurve] = BondingCurveClient.getBondingCurvePDA(
       mint,
       this.program.programId
     );
 
-    const curveData = await this.getBondingCurve(mint);
-    const creator = curveData.creator;
-
-    // Calculate expected tokens with slippage
-    const tokensOut = this.calculateBuy(
-      solAmount,
-      curveData.realSolReserves.toNumber() / LAMPORTS_PER_SOL,
-      curveData.tokensSold.toNumber() / 1e9
-    );
-    const minTokensOut = Math.floor(tokensOut * (10000 - slippageBps) / 10000 * 1e9);
+    // The program's own quote covers the curve's preset, fee tiers, referral and partial fills
+    const quote = await this.quoteBuy(mint, solAmount, buyer.publicKey, stakingProgramId, referrerWallet);
+    const minTokensOut = quote.tokensOut.muln(10000 - slippageBps).divn(10000);
 
     const curveTokenAccount = await getAssociatedTokenAddress(
       mint,
@@ -88,15 +235,17 @@ export class BondingCurveClient {
     return await this.program.methods
       .buy(
         new anchor.BN(solAmount * LAMPORTS_PER_SOL),
-        new anchor.BN(minTokensOut)
+        minTokensOut
       )
       .accounts({
+        config: BondingCurveClient.getConfigPDA(this.program.programId)[0],
         bondingCurve,
+        mint,
         curveTokenAccount,
+        solVault: BondingCurveClient.getSolVaultPDA(mint, this.program.programId)[0],
         buyerTokenAccount,
         buyer: buyer.publicKey,
-        creator,
-        platformWallet,
+        ...(await this.traderAccounts(mint, buyer.publicKey, referrerWallet, stakingProgramId)),
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -106,6 +255,113 @@ export class BondingCurveClient {
       .rpc();
   }
 
+  /**
+   * Sell tokens for SOL.
+   * Pass the staking program to count the seller's stake towards holder fee tiers.
+   */
+  async sellTokens(
+    mint: PublicKey,
+    seller: Keypair,
+    tokenAmount: number,
+    slippageBps: number = 500, // 5% slippage
+    referrerWallet?: PublicKey,
+    stakingProgramId?: PublicKey
+  ): Promise<string> {
+    const [bondingCurve] = BondingCurveClient.getBondingCurvePDA(
+      mint,
+      this.program.programId
+    );
+
+    const quote = await this.quoteSell(mint, tokenAmount, seller.publicKey, stakingProgramId, referrerWallet);
+    const minSolOut = quote.solOut.muln(10000 - slippageBps).divn(10000);
+
+    return await this.program.methods
+      .sell(new anchor.BN(tokenAmount * 1e9), minSolOut)
+      .accounts({
+        config: BondingCurveClient.getConfigPDA(this.program.programId)[0],
+        bondingCurve,
+        curveTokenAccount: await getAssociatedTokenAddress(mint, bondingCurve, true),
+        sellerTokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+        seller: seller.publicKey,
+        ...(await this.traderAccounts(mint, seller.publicKey, referrerWallet, stakingProgramId)),
+        solVault: BondingCurveClient.getSolVaultPDA(mint, this.program.programId)[0],
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([seller])
+      .rpc();
+  }
+
+  /**
+   * The optional accounts `buy` and `sell` take for the trader, left null when they do not exist
+   */
+  private async traderAccounts(
+    mint: PublicKey,
+    trader: PublicKey,
+    referrerWallet?: PublicKey,
+    stakingProgramId?: PublicKey
+  ) {
+    return {
+      referrer: referrerWallet
+        ? BondingCurveClient.getReferrerPDA(referrerWallet, this.program.programId)[0]
+        : null,
+      priceObservations: await this.getPriceObservations(mint),
+      userMintStats: await this.getUserStats(trader, mint),
+      userStats: await this.getUserStats(trader),
+      stakePosition: stakingProgramId
+        ? await this.getStakePosition(trader, stakingProgramId)
+        : null,
+    };
+  }
+
+  /**
+   * The curve's price observation buffer, or null if it has not been created
+   */
+  async getPriceObservations(mint: PublicKey): Promise<PublicKey | null> {
+    const [priceObservations] = BondingCurveClient.getPriceObservationsPDA(
+      mint,
+      this.program.programId
+    );
+    const info = await this.provider.connection.getAccountInfo(priceObservations);
+    return info ? priceObservations : null;
+  }
+
+  /**
+   * Create a wallet's trade stats, for one token when `mint` is given, otherwise across all tokens
+   */
+  async initializeUserStats(user: Keypair, mint?: PublicKey): Promise<string> {
+    return await this.program.methods
+      .initializeUserStats(mint ?? null)
+      .accounts({
+        userStats: BondingCurveClient.getUserStatsPDA(user.publicKey, this.program.programId, mint)[0],
+        user: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  /**
+   * A wallet's trade stats account, or null if it has not been created
+   */
+  async getUserStats(user: PublicKey, mint?: PublicKey): Promise<PublicKey | null> {
+    const [userStats] = BondingCurveClient.getUserStatsPDA(user, this.program.programId, mint);
+    const info = await this.provider.connection.getAccountInfo(userStats);
+    return info ? userStats : null;
+  }
+
+  /**
+   * A wallet's stake position in the staking program, or null if it has never staked
+   */
+  async getStakePosition(owner: PublicKey, stakingProgramId: PublicKey): Promise<PublicKey | null> {
+    const [position] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stake"), owner.toBuffer()],
+      stakingProgramId
+    );
+    const info = await this.provider.connection.getAccountInfo(position);
+    return info ? position : null;
+  }
+
   /**
    * Get bonding curve state
    */
@@ -118,7 +374,79 @@ export class BondingCurveClient {
   }
 
   /**
-   * Calculate tokens received for SOL input
+   * Authoritative buy quote from the program, computed by simulating `quote_buy`. Pass the
+   * trader to quote their fee tier discount, and their referrer to quote its cut.
+   */
+  async quoteBuy(
+    mint: PublicKey,
+    solAmount: number,
+    trader?: PublicKey,
+    stakingProgramId?: PublicKey,
+    referrerWallet?: PublicKey
+  ) {
+    return await this.program.methods
+      .quoteBuy(new anchor.BN(solAmount * LAMPORTS_PER_SOL))
+      .accounts(await this.quoteTradeAccounts(mint, trader, stakingProgramId, referrerWallet))
+      .view();
+  }
+
+  /**
+   * Authoritative sell quote from the program, computed by simulating `quote_sell`. Pass the
+   * trader to quote their fee tier discount, and their referrer to quote its cut.
+   */
+  async quoteSell(
+    mint: PublicKey,
+    tokenAmount: number,
+    trader?: PublicKey,
+    stakingProgramId?: PublicKey,
+    referrerWallet?: PublicKey
+  ) {
+    return await this.program.methods
+      .quoteSell(new anchor.BN(tokenAmount * 1e9))
+      .accounts(await this.quoteTradeAccounts(mint, trader, stakingProgramId, referrerWallet))
+      .view();
+  }
+
+  /**
+   * Price, reserves and graduation progress, computed by simulating `get_curve_state`
+   */
+  async getCurveState(mint: PublicKey) {
+    return await this.program.methods
+      .getCurveState()
+      .accounts(this.quoteAccounts(mint))
+      .view();
+  }
+
+  private quoteAccounts(mint: PublicKey) {
+    const programId = this.program.programId;
+    return {
+      config: BondingCurveClient.getConfigPDA(programId)[0],
+      bondingCurve: BondingCurveClient.getBondingCurvePDA(mint, programId)[0],
+    };
+  }
+
+  private async quoteTradeAccounts(
+    mint: PublicKey,
+    trader?: PublicKey,
+    stakingProgramId?: PublicKey,
+    referrerWallet?: PublicKey
+  ) {
+    return {
+      ...this.quoteAccounts(mint),
+      trader: trader ?? PublicKey.default,
+      referrer: referrerWallet
+        ? BondingCurveClient.getReferrerPDA(referrerWallet, this.program.programId)[0]
+        : null,
+      userStats: trader ? await this.getUserStats(trader) : null,
+      stakePosition: trader && stakingProgramId
+        ? await this.getStakePosition(trader, stakingProgramId)
+        : null,
+    };
+  }
+
+  /**
+   * Rough estimate of tokens received for SOL input on a Standard preset curve, before fees.
+   * Use `quoteBuy` for the amount a buy will actually fill.
    */
   calculateBuy(solIn: number, solReserves: number, tokensSold: number): number {
     const virtualSol = BONDING_CURVE_CONFIG.VIRTUAL_SOL_RESERVES / LAMPORTS_PER_SOL;
@@ -0,0 +1,190 @@
+//! Per-wallet trade stats and the platform fee tiers they qualify for, shared by the bonding
+//! curve and fee wrapper so both programs track volume and pick tiers the same way.
+
+use anchor_lang::prelude::*;
+
+/// A wallet's trading totals and its rolling 30-day volume window
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct TradeStats {
+    pub volume: u64,                    // SOL traded
+    pub trade_count: u64,
+    pub first_trade_at: i64,
+    pub last_trade_at: i64,
+    pub sol_in: u64,                    // SOL spent on buys, fees included
+    pub sol_out: u64,                   // SOL received from sells, after fees
+    pub period_start: i64,              // Start of the current volume period
+    pub period_volume: u64,             // Volume in the current period
+    pub previous_period_volume: u64,    // Volume in the period before it
+}
+
+impl TradeStats {
+    pub const VOLUME_PERIOD_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+    /// Trailing 30-day volume, counting the part of the previous period still inside the
+    /// window pro rata
+    pub fn volume_30d(&self, now: i64) -> u64 {
+        let period = Self::VOLUME_PERIOD_SECONDS;
+        let elapsed = now - self.period_start;
+        let (current, previous, into_period) = if elapsed >= 2 * period {
+            (0, 0, 0)
+        } else if elapsed >= period {
+            (0, self.period_volume, elapsed - period)
+        } else {
+            (self.period_volume, self.previous_period_volume, elapsed.max(0))
+        };
+        current + (previous as u128 * (period - into_period) as u128 / period as u128) as u64
+    }
+
+    fn roll_period(&mut self, now: i64) {
+        let period = Self::VOLUME_PERIOD_SECONDS;
+        let elapsed = now - self.period_start;
+        if elapsed >= 2 * period {
+            self.period_start = now;
+            self.period_volume = 0;
+            self.previous_period_volume = 0;
+        } else if elapsed >= period {
+            self.period_start += period;
+            self.previous_period_volume = self.period_volume;
+            self.period_volume = 0;
+        }
+    }
+
+    /// Record a trade. Returns `None` if a total would overflow.
+    pub fn record_trade(&mut self, volume: u64, sol_in: u64, sol_out: u64, now: i64) -> Option<()> {
+        if self.trade_count == 0 {
+            self.first_trade_at = now;
+        }
+        self.roll_period(now);
+        self.period_volume = self.period_volume.checked_add(volume)?;
+        self.volume = self.volume.checked_add(volume)?;
+        self.trade_count = self.trade_count.checked_add(1)?;
+        self.last_trade_at = now;
+        self.sol_in = self.sol_in.checked_add(sol_in)?;
+        self.sol_out = self.sol_out.checked_add(sol_out)?;
+        Some(())
+    }
+}
+
+/// Platform fee discount for traders above a 30-day volume or a staked platform token balance
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeeTier {
+    pub kind: FeeTierKind,
+    pub threshold: u64,                 // Lamports of 30-day volume, or platform token base units staked
+    pub platform_discount_bps: u16,     // Share of the platform fee waived
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FeeTierKind {
+    Volume,
+    Holder,                             // Platform tokens staked; wallet balances don't count
+}
+
+impl FeeTier {
+    pub const MAX_TIERS: usize = 8;
+
+    pub fn qualifies(&self, volume_30d: u64, staked: u64) -> bool {
+        match self.kind {
+            FeeTierKind::Volume => volume_30d >= self.threshold,
+            FeeTierKind::Holder => staked >= self.threshold,
+        }
+    }
+
+    /// A table of at most `MAX_TIERS` tiers, each with a threshold and a discount of at most 100%
+    pub fn is_valid_table(tiers: &[FeeTier]) -> bool {
+        tiers.len() <= Self::MAX_TIERS
+            && tiers
+                .iter()
+                .all(|tier| tier.threshold > 0 && tier.platform_discount_bps <= 10000)
+    }
+
+    /// The qualifying tier with the largest discount
+    pub fn best(tiers: &[FeeTier], volume_30d: u64, staked: u64) -> Option<FeeTier> {
+        tiers
+            .iter()
+            .filter(|tier| tier.qualifies(volume_30d, staked))
+            .max_by_key(|tier| tier.platform_discount_bps)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_first_trade_time() {
+        let mut stats = TradeStats::default();
+        stats.record_trade(1_000, 1_000, 0, 100).unwrap();
+        stats.record_trade(600, 0, 590, 200).unwrap();
+
+        assert_eq!(stats.first_trade_at, 100);
+        assert_eq!(stats.last_trade_at, 200);
+        assert_eq!(stats.trade_count, 2);
+        assert_eq!((stats.volume, stats.sol_in, stats.sol_out), (1_600, 1_000, 590));
+    }
+
+    #[test]
+    fn rejects_overflowing_totals() {
+        let mut stats = TradeStats {
+            volume: u64::MAX,
+            ..TradeStats::default()
+        };
+        assert!(stats.record_trade(1, 1, 0, 100).is_none());
+    }
+
+    #[test]
+    fn volume_window_decays_previous_period() {
+        let period = TradeStats::VOLUME_PERIOD_SECONDS;
+        let mut stats = TradeStats::default();
+        let start = 10 * period;
+        stats.record_trade(1_000, 1_000, 0, start).unwrap();
+
+        assert_eq!(stats.volume_30d(start), 1_000);
+        // Half of the first period is still inside the window halfway through the next one
+        assert_eq!(stats.volume_30d(start + period + period / 2), 500);
+        assert_eq!(stats.volume_30d(start + 2 * period), 0);
+
+        // A trade in the next period rolls the first one into the previous period
+        stats.record_trade(200, 200, 0, start + period).unwrap();
+        assert_eq!((stats.period_volume, stats.previous_period_volume), (200, 1_000));
+        assert_eq!(stats.volume_30d(start + period), 1_200);
+    }
+
+    #[test]
+    fn picks_largest_qualifying_tier() {
+        let tier = |kind, threshold, platform_discount_bps| FeeTier {
+            kind,
+            threshold,
+            platform_discount_bps,
+        };
+        let tiers = [
+            tier(FeeTierKind::Volume, 100, 2500),
+            tier(FeeTierKind::Volume, 1_000, 5000),
+            tier(FeeTierKind::Holder, 1_000_000, 3000),
+        ];
+
+        assert!(FeeTier::best(&tiers, 50, 0).is_none());
+        assert_eq!(
+            FeeTier::best(&tiers, 150, 1_000_000).map(|tier| tier.platform_discount_bps),
+            Some(3000)
+        );
+        assert_eq!(
+            FeeTier::best(&tiers, 1_000, 1_000_000).map(|tier| tier.platform_discount_bps),
+            Some(5000)
+        );
+    }
+
+    #[test]
+    fn validates_tier_tables() {
+        let tier = |threshold, platform_discount_bps| FeeTier {
+            kind: FeeTierKind::Volume,
+            threshold,
+            platform_discount_bps,
+        };
+
+        assert!(FeeTier::is_valid_table(&[tier(1, 10000)]));
+        assert!(!FeeTier::is_valid_table(&[tier(0, 1000)]));
+        assert!(!FeeTier::is_valid_table(&[tier(1, 10001)]));
+        assert!(!FeeTier::is_valid_table(&[tier(1, 1000); FeeTier::MAX_TIERS + 1]));
+    }
+}
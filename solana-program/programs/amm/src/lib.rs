@@ -0,0 +1,265 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+declare_id!("EktXtpe4bxv75yF53d7v6hue48CaZFSucjA3iXyuj3To");
+
+#[program]
+pub mod amm {
+    use super::*;
+
+    /// Create a constant-product pool for a token against wSOL, with PDA-owned vaults.
+    /// Used as fee_wrapper's configured AMM in tests and on localnet.
+    pub fn initialize_pool(ctx: Context<InitializePool>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.base_mint = ctx.accounts.base_mint.key();
+        pool.quote_mint = ctx.accounts.quote_mint.key();
+        pool.base_vault = ctx.accounts.base_vault.key();
+        pool.quote_vault = ctx.accounts.quote_vault.key();
+        pool.bump = ctx.bumps.pool;
+
+        emit!(PoolInitialized {
+            pool: pool.key(),
+            base_mint: pool.base_mint,
+            quote_mint: pool.quote_mint,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit liquidity into both vaults. No LP tokens are issued.
+    pub fn add_liquidity(ctx: Context<AddLiquidity>, base_amount: u64, quote_amount: u64) -> Result<()> {
+        require!(base_amount > 0 && quote_amount > 0, AmmError::InvalidAmount);
+
+        for (from, to, amount) in [
+            (&ctx.accounts.provider_base_account, &ctx.accounts.base_vault, base_amount),
+            (&ctx.accounts.provider_quote_account, &ctx.accounts.quote_vault, quote_amount),
+        ] {
+            let cpi_accounts = Transfer {
+                from: from.to_account_info(),
+                to: to.to_account_info(),
+                authority: ctx.accounts.provider.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            token::transfer(CpiContext::new(cpi_program, cpi_accounts), amount)?;
+        }
+
+        emit!(LiquidityAdded {
+            pool: ctx.accounts.pool.key(),
+            provider: ctx.accounts.provider.key(),
+            base_amount,
+            quote_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Swap `amount_in` from the user's source account into their destination account.
+    /// The direction follows which pool vault the source account's mint belongs to.
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, minimum_amount_out: u64) -> Result<()> {
+        require!(amount_in > 0, AmmError::InvalidAmount);
+
+        let pool = &ctx.accounts.pool;
+        let source_vault = &ctx.accounts.source_vault;
+        let destination_vault = &ctx.accounts.destination_vault;
+        let is_pool_pair = (source_vault.key() == pool.base_vault && destination_vault.key() == pool.quote_vault)
+            || (source_vault.key() == pool.quote_vault && destination_vault.key() == pool.base_vault);
+        require!(is_pool_pair, AmmError::InvalidVault);
+
+        let amount_out = swap_output(amount_in, source_vault.amount, destination_vault.amount)?;
+        require!(amount_out >= minimum_amount_out, AmmError::SlippageExceeded);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_source.to_account_info(),
+            to: source_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new(cpi_program, cpi_accounts), amount_in)?;
+
+        let seeds = &[
+            b"pool".as_ref(),
+            pool.base_mint.as_ref(),
+            pool.quote_mint.as_ref(),
+            &[pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: destination_vault.to_account_info(),
+            to: ctx.accounts.user_destination.to_account_info(),
+            authority: pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer), amount_out)?;
+
+        emit!(Swapped {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            source_mint: source_vault.mint,
+            amount_in,
+            amount_out,
+        });
+
+        Ok(())
+    }
+}
+
+/// Constant-product output for `amount_in`, rounded down
+pub fn swap_output(amount_in: u64, reserve_in: u64, reserve_out: u64) -> Result<u64> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::EmptyPool);
+    let amount_out =
+        reserve_out as u128 * amount_in as u128 / (reserve_in as u128 + amount_in as u128);
+    require!(amount_out > 0, AmmError::InvalidAmount);
+    Ok(amount_out as u64)
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Pool::INIT_SPACE,
+        seeds = [b"pool", base_mint.key().as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    pub base_mint: Account<'info, Mint>,
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"vault", pool.key().as_ref(), base_mint.key().as_ref()],
+        bump,
+        token::mint = base_mint,
+        token::authority = pool
+    )]
+    pub base_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"vault", pool.key().as_ref(), quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = pool
+    )]
+    pub quote_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(has_one = base_vault, has_one = quote_vault)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub base_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub quote_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.base_mint)]
+    pub provider_base_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.quote_mint)]
+    pub provider_quote_account: Account<'info, TokenAccount>,
+
+    pub provider: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, token::mint = source_vault.mint)]
+    pub user_source: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = destination_vault.mint)]
+    pub user_destination: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub source_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination_vault: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub base_mint: Pubkey,                // Traded token
+    pub quote_mint: Pubkey,               // wSOL
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub bump: u8,
+}
+
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+#[event]
+pub struct Swapped {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub source_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
+
+#[error_code]
+pub enum AmmError {
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Pool has no liquidity")]
+    EmptyPool,
+    #[msg("Vaults do not belong to this pool")]
+    InvalidVault,
+    #[msg("Slippage tolerance exceeded")]
+    SlippageExceeded,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_output_follows_constant_product() {
+        // 1_000 in against 10_000 / 10_000 leaves k unchanged up to rounding
+        let out = swap_output(1_000, 10_000, 10_000).unwrap();
+        assert_eq!(out, 909);
+        assert!((10_000 + 1_000) * (10_000 - out) >= 10_000 * 10_000);
+    }
+
+    #[test]
+    fn swap_output_rejects_empty_pool() {
+        assert_eq!(
+            swap_output(1_000, 0, 10_000).err(),
+            Some(AmmError::EmptyPool.into())
+        );
+    }
+}
@@ -1,23 +1,284 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer, MintTo};
 use anchor_spl::associated_token::AssociatedToken;
+use crate::program::BondingCurve as BondingCurveProgram;
+use fee_wrapper::program::FeeWrapper as FeeWrapperProgram;
+use staking::StakePosition;
+use trade_stats::{FeeTier, TradeStats};
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
 
-declare_id!("11111111111111111111111111111111"); // Will be updated after deployment
+declare_id!("GArRrFn6jMWas2WxkKCKwq8zGUkrAjEUq819G5qaA2Kg");
 
 #[program]
 pub mod bonding_curve {
     use super::*;
 
-    /// Initialize a new bonding curve for a token
+    /// Initialize the global config and the platform, prize pool and reserves fee vaults.
+    /// Only the program's upgrade authority can initialize; it becomes admin, pauser and
+    /// fee manager until roles are reassigned.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        platform_wallet: Pubkey,
+        prize_pool_wallet: Pubkey,
+        reserves_wallet: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let admin = ctx.accounts.admin.key();
+        config.admin = admin;
+        config.pending_admin = Pubkey::default();
+        config.pauser = admin;
+        config.fee_manager = admin;
+        config.platform_wallet = platform_wallet;
+        config.prize_pool_wallet = prize_pool_wallet;
+        config.reserves_wallet = reserves_wallet;
+        config.pre_graduation_fees = FeeSchedule::PRE_GRADUATION;
+        config.post_graduation_fees = FeeSchedule::POST_GRADUATION;
+        config.referral_share_bps = Config::DEFAULT_REFERRAL_SHARE_BPS;
+        config.config_delay_seconds = Config::DEFAULT_CONFIG_DELAY_SECONDS;
+        config.config_change_count = 0;
+        config.paused = false;
+        config.pause_allowlist = 0;
+        config.curve_presets = CurvePreset::defaults();
+        config.min_graduation_threshold = Config::DEFAULT_MIN_GRADUATION_THRESHOLD;
+        config.max_graduation_threshold = Config::DEFAULT_MAX_GRADUATION_THRESHOLD;
+        config.graduation_reward = Config::DEFAULT_GRADUATION_REWARD;
+        config.platform_token_mint = Pubkey::default();
+        config.fee_tiers = Vec::new();
+        config.bump = ctx.bumps.config;
+        config.reserved = [0; Config::RESERVED_BYTES];
+
+        init_fee_vault(&mut ctx.accounts.platform_vault, FeeBucket::Platform, ctx.bumps.platform_vault);
+        init_fee_vault(&mut ctx.accounts.prize_pool_vault, FeeBucket::PrizePool, ctx.bumps.prize_pool_vault);
+        init_fee_vault(&mut ctx.accounts.reserves_vault, FeeBucket::Reserves, ctx.bumps.reserves_vault);
+
+        emit!(ConfigInitialized {
+            admin,
+            platform_wallet,
+            prize_pool_wallet,
+            reserves_wallet,
+        });
+
+        Ok(())
+    }
+
+    /// Assign the pauser or fee manager role. The admin role moves via `transfer_admin`.
+    pub fn set_role(ctx: Context<AdminOnly>, role: Role, account: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        match role {
+            Role::Admin => return err!(BondingCurveError::InvalidRole),
+            Role::Pauser => config.pauser = account,
+            Role::FeeManager => config.fee_manager = account,
+        }
+
+        emit!(RoleUpdated { role, account });
+
+        Ok(())
+    }
+
+    /// Pause or unpause the whole program. Instructions in `allowlist` stay available while
+    /// paused, and the allowlist also applies to curves paused individually.
+    pub fn set_paused(
+        ctx: Context<PauserOnly>,
+        paused: bool,
+        allowlist: Vec<PausableInstruction>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.paused = paused;
+        config.pause_allowlist = PausableInstruction::mask(&allowlist);
+
+        emit!(PauseUpdated {
+            mint: None,
+            paused,
+            allowlist: config.pause_allowlist,
+        });
+
+        Ok(())
+    }
+
+    /// Pause or unpause a single curve
+    pub fn set_curve_paused(ctx: Context<SetCurvePaused>, paused: bool) -> Result<()> {
+        let curve = &mut ctx.accounts.bonding_curve;
+        curve.paused = paused;
+
+        emit!(PauseUpdated {
+            mint: Some(curve.mint),
+            paused,
+            allowlist: ctx.accounts.config.pause_allowlist,
+        });
+
+        Ok(())
+    }
+
+    /// Start a two-step admin transfer; the new admin must call `accept_admin`
+    pub fn transfer_admin(ctx: Context<AdminOnly>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.pending_admin = new_admin;
+
+        emit!(AdminTransferStarted {
+            admin: config.admin,
+            pending_admin: new_admin,
+        });
+
+        Ok(())
+    }
+
+    /// Complete a two-step admin transfer
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let previous_admin = config.admin;
+        config.admin = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin: config.admin,
+        });
+
+        Ok(())
+    }
+
+    /// Queue a config change that can be executed once the config delay has elapsed.
+    /// Fee rate and referral share changes may be queued by the fee manager, the rest by the admin.
+    pub fn queue_config_change(ctx: Context<QueueConfigChange>, change: ConfigChange) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let proposer = ctx.accounts.proposer.key();
+        require!(config.can_manage(&proposer, &change), BondingCurveError::Unauthorized);
+        change.validate()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let pending_change = &mut ctx.accounts.pending_change;
+        pending_change.id = config.config_change_count;
+        pending_change.change = change.clone();
+        pending_change.proposer = proposer;
+        pending_change.queued_at = now;
+        pending_change.eta = now
+            .checked_add(config.config_delay_seconds)
+            .ok_or(BondingCurveError::InvalidCalculation)?;
+        pending_change.bump = ctx.bumps.pending_change;
+
+        config.config_change_count += 1;
+
+        emit!(ConfigChangeQueued {
+            id: pending_change.id,
+            change,
+            proposer,
+            eta: pending_change.eta,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a queued config change after its delay. Permissionless, but the proposer must
+    /// still hold a role allowed to make the change.
+    pub fn execute_config_change(ctx: Context<ExecuteConfigChange>) -> Result<()> {
+        let pending_change = &ctx.accounts.pending_change;
+        pending_change.check_executable(&ctx.accounts.config, Clock::get()?.unix_timestamp)?;
+
+        let change = pending_change.change.clone();
+        change.validate()?;
+        change.apply(&mut ctx.accounts.config)?;
+
+        emit!(ConfigChangeExecuted {
+            id: pending_change.id,
+            change,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a queued config change. The admin can cancel anything; the fee manager
+    /// can cancel the changes it is allowed to queue.
+    pub fn cancel_config_change(ctx: Context<CancelConfigChange>) -> Result<()> {
+        let pending_change = &ctx.accounts.pending_change;
+        let canceller = ctx.accounts.canceller.key();
+        require!(
+            ctx.accounts.config.can_manage(&canceller, &pending_change.change),
+            BondingCurveError::Unauthorized
+        );
+
+        emit!(ConfigChangeCancelled {
+            id: pending_change.id,
+            cancelled_by: canceller,
+        });
+
+        Ok(())
+    }
+
+    /// Register a wallet as a referrer so it can be passed to `buy` and `sell`
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        let referrer = &mut ctx.accounts.referrer;
+        referrer.wallet = ctx.accounts.wallet.key();
+        referrer.referred_volume = 0;
+        referrer.referred_trades = 0;
+        referrer.earnings_accrued = 0;
+        referrer.earnings_claimed = 0;
+        referrer.bump = ctx.bumps.referrer;
+
+        emit!(ReferrerRegistered {
+            wallet: referrer.wallet,
+        });
+
+        Ok(())
+    }
+
+    /// Create a wallet's trade stats, for one token when `mint` is passed, otherwise across all tokens.
+    /// Pass them to `buy` and `sell` to keep them updated.
+    pub fn initialize_user_stats(ctx: Context<InitializeUserStats>, mint: Option<Pubkey>) -> Result<()> {
+        let stats = &mut ctx.accounts.user_stats;
+        stats.user = ctx.accounts.user.key();
+        stats.mint = mint.unwrap_or_default();
+        stats.stats = TradeStats::default();
+        stats.bump = ctx.bumps.user_stats;
+        stats.reserved = [0; UserStats::RESERVED_BYTES];
+        Ok(())
+    }
+
+    /// Claim accrued referral earnings
+    pub fn claim_referral_earnings(ctx: Context<ClaimReferralEarnings>) -> Result<()> {
+        let referrer = &mut ctx.accounts.referrer;
+
+        let amount = referrer.earnings_accrued - referrer.earnings_claimed;
+        require!(amount > 0, BondingCurveError::NoFeesToClaim);
+        referrer.earnings_claimed += amount;
+
+        transfer_lamports(&referrer.to_account_info(), &ctx.accounts.wallet.to_account_info(), amount)?;
+
+        emit!(ReferralEarningsClaimed {
+            wallet: referrer.wallet,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize a new bonding curve for a token from one of the config's curve presets
     /// Mints the entire bonding curve supply to the contract
+    /// `graduation_threshold` is the real SOL to raise before graduating, defaulting to the preset's
     pub fn initialize_curve(
         ctx: Context<InitializeCurve>,
-        virtual_sol_reserves: u64,
-        virtual_token_reserves: u64,
-        bonding_curve_supply: u64,
+        preset_id: u8,
+        graduation_threshold: Option<u64>,
     ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let preset = config.curve_preset(preset_id)?.clone();
+        preset.validate()?;
+        let graduation_threshold = graduation_threshold.unwrap_or(preset.graduation_threshold);
+        config.validate_graduation_threshold(graduation_threshold)?;
+        require!(
+            graduation_threshold <= preset.max_sol_raised(),
+            BondingCurveError::InvalidGraduationThreshold
+        );
+        let CurvePreset {
+            virtual_sol_reserves,
+            virtual_token_reserves,
+            bonding_curve_supply,
+            ..
+        } = preset;
+
         let curve = &mut ctx.accounts.bonding_curve;
         
+        curve.version = BondingCurve::CURRENT_VERSION;
         curve.mint = ctx.accounts.mint.key();
         curve.creator = ctx.accounts.creator.key();
         curve.virtual_sol_reserves = virtual_sol_reserves;
@@ -26,10 +287,29 @@ pub mod bonding_curve {
         curve.real_token_reserves = bonding_curve_supply;
         curve.tokens_sold = 0;
         curve.is_graduated = false;
-        curve.graduation_threshold = 326 * LAMPORTS_PER_SOL; // 326 SOL (~$75k market cap)
+        curve.graduation_threshold = graduation_threshold;
         curve.total_fees_collected = 0;
         curve.creator_fees_pending = 0;
+        curve.platform_fees_pending = 0;
+        curve.prize_pool_fees_pending = 0;
+        curve.reserves_fees_pending = 0;
+        curve.paused = false;
         curve.bump = ctx.bumps.bonding_curve;
+        curve.sol_vault_bump = ctx.bumps.sol_vault;
+        curve.graduation_completed = false;
+        curve.price_cumulative = 0;
+        curve.last_update_slot = Clock::get()?.slot;
+        curve.reserved = [0; BondingCurve::RESERVED_BYTES];
+
+        // Fund the SOL vault up to rent exemption so it can hold reserves
+        let cpi_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: ctx.accounts.creator.to_account_info(),
+                to: ctx.accounts.sol_vault.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_context, Rent::get()?.minimum_balance(0))?;
 
         // Mint the entire bonding curve supply to the curve's token account
         let cpi_accounts = MintTo {
@@ -44,9 +324,11 @@ pub mod bonding_curve {
         emit!(CurveInitialized {
             mint: curve.mint,
             creator: curve.creator,
+            preset_id,
             virtual_sol_reserves,
             virtual_token_reserves,
             bonding_curve_supply,
+            graduation_threshold,
         });
 
         Ok(())
@@ -56,96 +338,69 @@ pub mod bonding_curve {
     pub fn buy(ctx: Context<BuyTokens>, sol_amount: u64, min_tokens_out: u64) -> Result<()> {
         let curve = &mut ctx.accounts.bonding_curve;
         
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::Buy)?;
         require!(!curve.is_graduated, BondingCurveError::TokenGraduated);
         require!(sol_amount > 0, BondingCurveError::InvalidAmount);
+        curve.update_price_accumulator(Clock::get()?.slot);
 
-        // Calculate tokens to receive
-        let tokens_out = calculate_buy_tokens(
-            sol_amount,
-            curve.virtual_sol_reserves + curve.real_sol_reserves,
-            curve.virtual_token_reserves - curve.tokens_sold,
-        )?;
+        let now = Clock::get()?.unix_timestamp;
+        let fee_tier = trader_fee_tier(
+            &ctx.accounts.config,
+            ctx.accounts.user_stats.as_deref(),
+            ctx.accounts.stake_position.as_deref(),
+            now,
+        );
+        let schedule = ctx
+            .accounts
+            .config
+            .fee_schedule(curve.is_graduated)
+            .with_platform_discount(fee_tier.map_or(0, |tier| tier.platform_discount_bps));
+
+        // Fill the buy, only up to the graduation threshold or the remaining curve supply
+        let BuyFill {
+            sol_in,
+            sol_to_curve,
+            tokens_out,
+            mut fees,
+            completes_curve,
+        } = curve.fill_buy(&schedule, sol_amount)?;
+        let sol_refunded = sol_amount - sol_in;
 
         require!(tokens_out >= min_tokens_out, BondingCurveError::SlippageExceeded);
-        require!(tokens_out <= curve.real_token_reserves, BondingCurveError::InsufficientTokens);
-
-        // Calculate fees based on graduation status
-        let (platform_fee_bps, creator_fee_bps, prize_pool_fee_bps, reserves_fee_bps) = 
-            if curve.is_graduated {
-                (50u16, 100u16, 30u16, 20u16)   // Post-graduation: 0.5% platform, 1% creator, 0.3% prize, 0.2% reserves
-            } else {
-                (100u16, 50u16, 30u16, 20u16)   // Pre-graduation: 1% platform, 0.5% creator, 0.3% prize, 0.2% reserves
-            };
-            
-        let platform_fee = (sol_amount as u128 * platform_fee_bps as u128 / 10000) as u64;
-        let creator_fee = (sol_amount as u128 * creator_fee_bps as u128 / 10000) as u64;
-        let prize_pool_fee = (sol_amount as u128 * prize_pool_fee_bps as u128 / 10000) as u64;
-        let reserves_fee = (sol_amount as u128 * reserves_fee_bps as u128 / 10000) as u64;
-        let total_fees = platform_fee + creator_fee + prize_pool_fee + reserves_fee;
-        let sol_to_curve = sol_amount - total_fees;
-
-        // Transfer SOL from buyer to curve (minus fees)
+
+        if let Some(referrer) = &ctx.accounts.referrer {
+            require!(referrer.wallet != ctx.accounts.buyer.key(), BondingCurveError::SelfReferral);
+            fees.apply_referral(ctx.accounts.config.referral_share_bps);
+        }
+
+        // Transfer the filled SOL from buyer to the SOL vault in a single transfer; the unfilled
+        // remainder is never taken. Fees stay in the vault as pending balances until swept.
         let cpi_context = CpiContext::new(
             ctx.accounts.system_program.to_account_info(),
             anchor_lang::system_program::Transfer {
                 from: ctx.accounts.buyer.to_account_info(),
-                to: ctx.accounts.bonding_curve.to_account_info(),
+                to: ctx.accounts.sol_vault.to_account_info(),
             },
         );
-        anchor_lang::system_program::transfer(cpi_context, sol_to_curve)?;
-
-        // Transfer platform fee (1%)
-        if platform_fee > 0 {
-            let platform_transfer = CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.platform_wallet.to_account_info(),
-                },
-            );
-            anchor_lang::system_program::transfer(platform_transfer, platform_fee)?;
-        }
-
-        // Transfer creator fee (0.07%) - automatically distributed
-        if creator_fee > 0 {
-            let creator_transfer = CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.creator.to_account_info(),
-                },
-            );
-            anchor_lang::system_program::transfer(creator_transfer, creator_fee)?;
-        }
-
-        // Transfer prize pool fee (0.02%)
-        if prize_pool_fee > 0 {
-            let prize_pool_transfer = CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.prize_pool_wallet.to_account_info(),
-                },
-            );
-            anchor_lang::system_program::transfer(prize_pool_transfer, prize_pool_fee)?;
-        }
-
-        // Transfer reserves fee (0.01%)
-        if reserves_fee > 0 {
-            let reserves_transfer = CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.reserves_wallet.to_account_info(),
-                },
-            );
-            anchor_lang::system_program::transfer(reserves_transfer, reserves_fee)?;
+        anchor_lang::system_program::transfer(cpi_context, sol_in)?;
+
+        // Pay the referrer's cut of the platform fee
+        if let Some(referrer) = ctx.accounts.referrer.as_mut() {
+            transfer_from_sol_vault(
+                curve,
+                &ctx.accounts.sol_vault,
+                referrer.to_account_info(),
+                &ctx.accounts.system_program,
+                fees.referral,
+            )?;
+            record_referral(referrer, fees.referral, sol_in);
         }
 
         // Transfer tokens from curve to buyer
+        let mint_key = curve.mint;
         let seeds = &[
             b"bonding_curve",
-            curve.mint.as_ref(),
+            mint_key.as_ref(),
             &[curve.bump],
         ];
         let signer = &[&seeds[..]];
@@ -153,7 +408,7 @@ pub mod bonding_curve {
         let cpi_accounts = Transfer {
             from: ctx.accounts.curve_token_account.to_account_info(),
             to: ctx.accounts.buyer_token_account.to_account_info(),
-            authority: ctx.accounts.bonding_curve.to_account_info(),
+            authority: curve.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
@@ -163,16 +418,28 @@ pub mod bonding_curve {
         curve.real_sol_reserves += sol_to_curve;
         curve.real_token_reserves -= tokens_out;
         curve.tokens_sold += tokens_out;
-        curve.total_fees_collected += total_fees;
-        curve.creator_fees_pending += creator_fee;
+        curve.accrue_fees(&fees);
+        assert_curve_solvent(curve, &ctx.accounts.sol_vault)?;
+        if let Some(observations) = ctx.accounts.price_observations.as_mut() {
+            observations.record(curve);
+        }
+        for stats in [ctx.accounts.user_mint_stats.as_deref_mut(), ctx.accounts.user_stats.as_deref_mut()]
+            .into_iter()
+            .flatten()
+        {
+            stats
+                .stats
+                .record_trade(sol_in, sol_in, 0, now)
+                .ok_or(BondingCurveError::InvalidCalculation)?;
+        }
 
-        // Check for graduation
-        let total_sol_value = curve.real_sol_reserves + curve.virtual_sol_reserves;
-        if total_sol_value >= curve.graduation_threshold && !curve.is_graduated {
+        // Graduate in the same instruction as the buy that completes the curve
+        if completes_curve || curve.real_sol_reserves >= curve.graduation_threshold {
             curve.is_graduated = true;
             emit!(TokenGraduated {
                 mint: curve.mint,
-                total_sol_raised: total_sol_value,
+                total_sol_raised: curve.real_sol_reserves,
+                graduation_threshold: curve.graduation_threshold,
                 tokens_sold: curve.tokens_sold,
             });
         }
@@ -180,45 +447,56 @@ pub mod bonding_curve {
         emit!(TokensPurchased {
             buyer: ctx.accounts.buyer.key(),
             mint: curve.mint,
-            sol_amount,
+            sol_amount: sol_in,
+            sol_refunded,
             tokens_received: tokens_out,
-            platform_fee,
-            creator_fee,
-            prize_pool_fee,
-            reserves_fee,
+            platform_fee: fees.platform,
+            creator_fee: fees.creator,
+            prize_pool_fee: fees.prize_pool,
+            reserves_fee: fees.reserves,
+            referrer: ctx.accounts.referrer.as_ref().map(|referrer| referrer.wallet),
+            referral_fee: fees.referral,
+            fee_tier,
         });
 
         Ok(())
     }
 
-    /// Sell tokens for SOL
+    /// Sell tokens for SOL. `min_sol_out` bounds what the seller receives after fees
     pub fn sell(ctx: Context<SellTokens>, token_amount: u64, min_sol_out: u64) -> Result<()> {
         let curve = &mut ctx.accounts.bonding_curve;
         
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::Sell)?;
         require!(!curve.is_graduated, BondingCurveError::TokenGraduated);
         require!(token_amount > 0, BondingCurveError::InvalidAmount);
+        curve.update_price_accumulator(Clock::get()?.slot);
 
-        // Calculate SOL to receive
-        let sol_out = calculate_sell_sol(
-            token_amount,
-            curve.virtual_sol_reserves + curve.real_sol_reserves,
-            curve.virtual_token_reserves - curve.tokens_sold,
-        )?;
+        let now = Clock::get()?.unix_timestamp;
+        let fee_tier = trader_fee_tier(
+            &ctx.accounts.config,
+            ctx.accounts.user_stats.as_deref(),
+            ctx.accounts.stake_position.as_deref(),
+            now,
+        );
+        let schedule = ctx
+            .accounts
+            .config
+            .fee_schedule(curve.is_graduated)
+            .with_platform_discount(fee_tier.map_or(0, |tier| tier.platform_discount_bps));
 
-        require!(sol_out >= min_sol_out, BondingCurveError::SlippageExceeded);
-        require!(sol_out <= curve.real_sol_reserves, BondingCurveError::InsufficientSol);
+        // Calculate SOL to receive, with the same fee split as buys
+        let SellFill {
+            sol_out,
+            sol_to_seller,
+            mut fees,
+        } = curve.fill_sell(&schedule, token_amount)?;
 
-        // Calculate fees based on graduation status
-        let (platform_fee_bps, creator_fee_bps) = 
-            if curve.is_graduated {
-                (50u16, 100u16)   // Post-graduation: 0.5% platform, 1% creator
-            } else {
-                (100u16, 50u16)   // Pre-graduation: 1% platform, 0.5% creator
-            };
-            
-        let creator_fee = (sol_out as u128 * creator_fee_bps as u128 / 10000) as u64;
-        let platform_fee = (sol_out as u128 * platform_fee_bps as u128 / 10000) as u64;
-        let sol_to_seller = sol_out - creator_fee - platform_fee;
+        require!(sol_to_seller >= min_sol_out, BondingCurveError::SlippageExceeded);
+
+        if let Some(referrer) = &ctx.accounts.referrer {
+            require!(referrer.wallet != ctx.accounts.seller.key(), BondingCurveError::SelfReferral);
+            fees.apply_referral(ctx.accounts.config.referral_share_bps);
+        }
 
         // Transfer tokens from seller to curve
         let cpi_accounts = Transfer {
@@ -230,43 +508,59 @@ pub mod bonding_curve {
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         token::transfer(cpi_ctx, token_amount)?;
 
-        // Transfer SOL from curve to seller (minus fees)
-        let seeds = &[
-            b"bonding_curve",
-            curve.mint.as_ref(),
-            &[curve.bump],
-        ];
-        let signer = &[&seeds[..]];
-
-        **ctx.accounts.bonding_curve.to_account_info().try_borrow_mut_lamports()? -= sol_to_seller;
-        **ctx.accounts.seller.to_account_info().try_borrow_mut_lamports()? += sol_to_seller;
-
-        // Send creator fee
-        if creator_fee > 0 {
-            **ctx.accounts.bonding_curve.to_account_info().try_borrow_mut_lamports()? -= creator_fee;
-            **ctx.accounts.creator.to_account_info().try_borrow_mut_lamports()? += creator_fee;
-        }
+        // Transfer SOL from the SOL vault to seller (minus fees).
+        // Fees stay in the vault as pending balances until swept to the fee vaults.
+        transfer_from_sol_vault(
+            curve,
+            &ctx.accounts.sol_vault,
+            ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.system_program,
+            sol_to_seller,
+        )?;
 
-        // Send platform fee
-        if platform_fee > 0 {
-            **ctx.accounts.bonding_curve.to_account_info().try_borrow_mut_lamports()? -= platform_fee;
-            **ctx.accounts.platform_wallet.to_account_info().try_borrow_mut_lamports()? += platform_fee;
+        // Pay the referrer's cut of the platform fee
+        if let Some(referrer) = ctx.accounts.referrer.as_mut() {
+            transfer_from_sol_vault(
+                curve,
+                &ctx.accounts.sol_vault,
+                referrer.to_account_info(),
+                &ctx.accounts.system_program,
+                fees.referral,
+            )?;
+            record_referral(referrer, fees.referral, sol_out);
         }
 
         // Update curve state
         curve.real_sol_reserves -= sol_out;
         curve.real_token_reserves += token_amount;
         curve.tokens_sold -= token_amount;
-        curve.total_fees_collected += creator_fee + platform_fee;
-        curve.creator_fees_pending += creator_fee;
+        curve.accrue_fees(&fees);
+        assert_curve_solvent(curve, &ctx.accounts.sol_vault)?;
+        if let Some(observations) = ctx.accounts.price_observations.as_mut() {
+            observations.record(curve);
+        }
+        for stats in [ctx.accounts.user_mint_stats.as_deref_mut(), ctx.accounts.user_stats.as_deref_mut()]
+            .into_iter()
+            .flatten()
+        {
+            stats
+                .stats
+                .record_trade(sol_out, 0, sol_to_seller, now)
+                .ok_or(BondingCurveError::InvalidCalculation)?;
+        }
 
         emit!(TokensSold {
             seller: ctx.accounts.seller.key(),
             mint: curve.mint,
             token_amount,
             sol_received: sol_to_seller,
-            creator_fee,
-            platform_fee,
+            platform_fee: fees.platform,
+            creator_fee: fees.creator,
+            prize_pool_fee: fees.prize_pool,
+            reserves_fee: fees.reserves,
+            referrer: ctx.accounts.referrer.as_ref().map(|referrer| referrer.wallet),
+            referral_fee: fees.referral,
+            fee_tier,
         });
 
         Ok(())
@@ -274,15 +568,56 @@ pub mod bonding_curve {
 
     /// Graduate token to external DEX (Raydium)
     /// This creates a liquidity pool with remaining tokens and SOL
+    /// Permissionless crank once the curve has graduated; the caller is paid the config's
+    /// graduation reward out of the reserves fee vault, as far as it can cover it.
+    /// Creates the token's fee wrapper for the curve's creator with the post-graduation fees.
     pub fn graduate(ctx: Context<Graduate>) -> Result<()> {
         let curve = &mut ctx.accounts.bonding_curve;
         
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::Graduate)?;
         require!(curve.is_graduated, BondingCurveError::NotGraduated);
-        require!(
-            ctx.accounts.authority.key() == curve.creator || 
-            ctx.accounts.authority.key() == ctx.accounts.platform_wallet.key(),
-            BondingCurveError::Unauthorized
-        );
+        require!(!curve.graduation_completed, BondingCurveError::AlreadyGraduated);
+        curve.graduation_completed = true;
+
+        let reserves_vault = &mut ctx.accounts.reserves_vault;
+        let reward = ctx
+            .accounts
+            .config
+            .graduation_reward
+            .min(reserves_vault.total_swept - reserves_vault.total_claimed);
+        if reward > 0 {
+            reserves_vault.total_claimed += reward;
+            transfer_lamports(
+                &reserves_vault.to_account_info(),
+                &ctx.accounts.cranker.to_account_info(),
+                reward,
+            )?;
+        }
+
+        // Hand the token over to the fee wrapper, unless the admin already created it.
+        // Every non-creator bucket goes to the platform side.
+        if ctx.accounts.wrapper.data_is_empty() {
+            let fees = ctx.accounts.config.post_graduation_fees;
+            let creator_fee_bps = fees.creator_bps;
+            let platform_fee_bps = (fees.total_bps() - creator_fee_bps as u32) as u16;
+            let seeds = &[b"graduation_authority".as_ref(), &[ctx.bumps.graduation_authority]];
+            let signer = &[&seeds[..]];
+            let cpi_accounts = fee_wrapper::cpi::accounts::InitializeWrapper {
+                wrapper_config: ctx.accounts.wrapper_config.to_account_info(),
+                wrapper: ctx.accounts.wrapper.to_account_info(),
+                authority: ctx.accounts.graduation_authority.to_account_info(),
+                payer: ctx.accounts.cranker.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.fee_wrapper_program.to_account_info();
+            fee_wrapper::cpi::initialize_wrapper(
+                CpiContext::new_with_signer(cpi_program, cpi_accounts, signer),
+                curve.mint,
+                curve.creator,
+                platform_fee_bps,
+                creator_fee_bps,
+            )?;
+        }
 
         // In production, this would:
         // 1. Create a Raydium AMM pool
@@ -294,15 +629,20 @@ pub mod bonding_curve {
             mint: curve.mint,
             final_sol_reserves: curve.real_sol_reserves,
             remaining_tokens: curve.real_token_reserves,
+            cranker: ctx.accounts.cranker.key(),
+            reward,
+            wrapper: ctx.accounts.wrapper.key(),
         });
 
         Ok(())
     }
 
-    /// Claim pending creator fees
+    /// Claim pending creator fees.
+    /// Creator fees are only ever accrued on the curve and paid out here, never sent during trades.
     pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
         let curve = &mut ctx.accounts.bonding_curve;
         
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::ClaimCreatorFees)?;
         require!(ctx.accounts.creator.key() == curve.creator, BondingCurveError::Unauthorized);
         require!(curve.creator_fees_pending > 0, BondingCurveError::NoFeesToClaim);
 
@@ -310,8 +650,14 @@ pub mod bonding_curve {
         curve.creator_fees_pending = 0;
 
         // Transfer pending fees to creator
-        **ctx.accounts.bonding_curve.to_account_info().try_borrow_mut_lamports()? -= fees_to_claim;
-        **ctx.accounts.creator.to_account_info().try_borrow_mut_lamports()? += fees_to_claim;
+        transfer_from_sol_vault(
+            curve,
+            &ctx.accounts.sol_vault,
+            ctx.accounts.creator.to_account_info(),
+            &ctx.accounts.system_program,
+            fees_to_claim,
+        )?;
+        assert_curve_solvent(curve, &ctx.accounts.sol_vault)?;
 
         emit!(CreatorFeesClaimed {
             creator: curve.creator,
@@ -320,193 +666,2171 @@ pub mod bonding_curve {
 
         Ok(())
     }
-}
 
-// Math functions
-fn calculate_buy_tokens(sol_in: u64, sol_reserves: u64, token_reserves: u64) -> Result<u64> {
-    let k = (sol_reserves as u128) * (token_reserves as u128);
-    let new_sol_reserves = sol_reserves + sol_in;
-    let new_token_reserves = k / (new_sol_reserves as u128);
-    let tokens_out = token_reserves - (new_token_reserves as u64);
-    
-    require!(tokens_out > 0, BondingCurveError::InvalidCalculation);
-    Ok(tokens_out)
-}
+    /// Move a curve's pending platform, prize pool and reserves fees into the fee vaults.
+    /// Permissionless: the lamports can only ever reach the vaults.
+    pub fn sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+        let curve = &mut ctx.accounts.bonding_curve;
 
-fn calculate_sell_sol(token_in: u64, sol_reserves: u64, token_reserves: u64) -> Result<u64> {
-    let k = (sol_reserves as u128) * (token_reserves as u128);
-    let new_token_reserves = token_reserves + token_in;
-    let new_sol_reserves = k / (new_token_reserves as u128);
-    let sol_out = sol_reserves - (new_sol_reserves as u64);
-    
-    require!(sol_out > 0, BondingCurveError::InvalidCalculation);
-    Ok(sol_out)
-}
+        let platform_amount = curve.platform_fees_pending;
+        let prize_pool_amount = curve.prize_pool_fees_pending;
+        let reserves_amount = curve.reserves_fees_pending;
+        require!(
+            platform_amount > 0 || prize_pool_amount > 0 || reserves_amount > 0,
+            BondingCurveError::NoFeesToClaim
+        );
 
-// Account structs
-#[derive(Accounts)]
-pub struct InitializeCurve<'info> {
-    #[account(
-        init,
-        payer = creator,
-        space = 8 + BondingCurve::LEN,
-        seeds = [b"bonding_curve", mint.key().as_ref()],
-        bump
-    )]
-    pub bonding_curve: Account<'info, BondingCurve>,
-    
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
-    
-    #[account(
-        init,
-        payer = creator,
-        associated_token::mint = mint,
-        associated_token::authority = bonding_curve
-    )]
-    pub curve_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        curve.platform_fees_pending = 0;
+        curve.prize_pool_fees_pending = 0;
+        curve.reserves_fees_pending = 0;
 
-#[derive(Accounts)]
-pub struct BuyTokens<'info> {
-    #[account(
-        mut,
-        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
-        bump = bonding_curve.bump
-    )]
-    pub bonding_curve: Account<'info, BondingCurve>,
-    
-    #[account(
-        mut,
-        associated_token::mint = bonding_curve.mint,
-        associated_token::authority = bonding_curve
-    )]
-    pub curve_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        init_if_needed,
-        payer = buyer,
-        associated_token::mint = bonding_curve.mint,
-        associated_token::authority = buyer
-    )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-    
-    /// CHECK: Creator wallet for fee distribution
-    #[account(mut, address = bonding_curve.creator)]
-    pub creator: UncheckedAccount<'info>,
-    
-    /// CHECK: Platform wallet for fee distribution
-    #[account(mut)]
-    pub platform_wallet: UncheckedAccount<'info>,
-    
-    /// CHECK: Prize pool wallet for fee distribution
-    #[account(mut)]
-    pub prize_pool_wallet: UncheckedAccount<'info>,
-    
-    /// CHECK: Reserves wallet for fee distribution
-    #[account(mut)]
-    pub reserves_wallet: UncheckedAccount<'info>,
-    
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        for (fee_vault, amount) in [
+            (&mut ctx.accounts.platform_vault, platform_amount),
+            (&mut ctx.accounts.prize_pool_vault, prize_pool_amount),
+            (&mut ctx.accounts.reserves_vault, reserves_amount),
+        ] {
+            transfer_from_sol_vault(
+                curve,
+                &ctx.accounts.sol_vault,
+                fee_vault.to_account_info(),
+                &ctx.accounts.system_program,
+                amount,
+            )?;
+            fee_vault.total_swept += amount;
+        }
+        assert_curve_solvent(curve, &ctx.accounts.sol_vault)?;
 
-#[derive(Accounts)]
-pub struct SellTokens<'info> {
-    #[account(
-        mut,
-        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
-        bump = bonding_curve.bump
-    )]
-    pub bonding_curve: Account<'info, BondingCurve>,
-    
-    #[account(
-        mut,
-        associated_token::mint = bonding_curve.mint,
-        associated_token::authority = bonding_curve
-    )]
-    pub curve_token_account: Account<'info, TokenAccount>,
-    
-    #[account(
-        mut,
-        associated_token::mint = bonding_curve.mint,
-        associated_token::authority = seller
-    )]
-    pub seller_token_account: Account<'info, TokenAccount>,
-    
-    #[account(mut)]
-    pub seller: Signer<'info>,
-    
-    /// CHECK: Creator wallet for fee distribution
-    #[account(mut, address = bonding_curve.creator)]
-    pub creator: UncheckedAccount<'info>,
-    
-    /// CHECK: Platform wallet for fee distribution
-    #[account(mut)]
-    pub platform_wallet: UncheckedAccount<'info>,
-    
-    pub token_program: Program<'info, Token>,
-}
+        emit!(FeesSwept {
+            mint: curve.mint,
+            platform_amount,
+            prize_pool_amount,
+            reserves_amount,
+        });
 
+        Ok(())
+    }
+
+    /// Pay out a fee vault's unclaimed balance to its configured recipient.
+    /// Permissionless: the recipient is fixed by the config.
+    pub fn claim_fees(ctx: Context<ClaimFees>, bucket: FeeBucket) -> Result<()> {
+        let vault = &mut ctx.accounts.fee_vault;
+
+        let amount = vault.total_swept - vault.total_claimed;
+        require!(amount > 0, BondingCurveError::NoFeesToClaim);
+        vault.total_claimed += amount;
+
+        transfer_lamports(&vault.to_account_info(), &ctx.accounts.recipient.to_account_info(), amount)?;
+
+        emit!(FeesClaimed {
+            bucket,
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Upgrade a curve account to the current version, reallocating it in place and moving
+    /// any SOL held on the curve account above rent into its SOL vault. Pending fees the vault
+    /// can't back (creator fees accrued while also being paid out) are written off. A curve that
+    /// already holds its converted graduation threshold graduates.
+    /// The payer covers the extra rent. Permissionless.
+    pub fn migrate_curve(ctx: Context<MigrateCurve>) -> Result<()> {
+        let curve_info = ctx.accounts.bonding_curve.to_account_info();
+        require!(curve_info.owner == &crate::ID, BondingCurveError::InvalidCurveAccount);
+
+        let old_len = curve_info.data_len();
+        let (mut curve, from_version) = upgrade_curve_data(&curve_info.try_borrow_data()?)?;
+        curve.sol_vault_bump = ctx.bumps.sol_vault;
+        let graduated = curve.graduate_if_threshold_reached();
+        let new_len = 8 + BondingCurve::INIT_SPACE;
+
+        let rent = Rent::get()?;
+        let rent_top_up = rent
+            .minimum_balance(new_len)
+            .saturating_sub(rent.minimum_balance(old_len));
+        let vault_rent_top_up = rent
+            .minimum_balance(0)
+            .saturating_sub(ctx.accounts.sol_vault.lamports());
+        for (to, amount) in [
+            (curve_info.clone(), rent_top_up),
+            (ctx.accounts.sol_vault.to_account_info(), vault_rent_top_up),
+        ] {
+            if amount > 0 {
+                let cpi_context = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to,
+                    },
+                );
+                anchor_lang::system_program::transfer(cpi_context, amount)?;
+            }
+        }
+
+        // Reserves and pending fees used to live on the curve account itself
+        let moved_to_vault = curve_info.lamports() - rent.minimum_balance(new_len);
+        let vault_lamports = ctx.accounts.sol_vault.lamports() + moved_to_vault;
+        let fees_written_off = curve.cap_pending_fees(
+            vault_lamports.saturating_sub(rent.minimum_balance(0) + curve.real_sol_reserves),
+        );
+
+        curve_info.realloc(new_len, true)?;
+        curve.try_serialize(&mut &mut curve_info.try_borrow_mut_data()?[..])?;
+        transfer_lamports(&curve_info, &ctx.accounts.sol_vault.to_account_info(), moved_to_vault)?;
+
+        emit!(CurveMigrated {
+            mint: curve.mint,
+            from_version,
+            to_version: curve.version,
+            moved_to_vault,
+            fees_written_off,
+        });
+        if graduated {
+            emit!(TokenGraduated {
+                mint: curve.mint,
+                total_sol_raised: curve.real_sol_reserves,
+                graduation_threshold: curve.graduation_threshold,
+                tokens_sold: curve.tokens_sold,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reconcile a curve whose creator fees were both sent to the creator during trades and
+    /// accrued as pending. Caps `creator_fees_pending` at what the SOL vault actually holds above
+    /// rent, reserves and other pending fees, and reports any shortfall that remains.
+    pub fn reconcile_creator_fees(ctx: Context<ReconcileCreatorFees>) -> Result<()> {
+        let curve = &mut ctx.accounts.bonding_curve;
+
+        let lamports = ctx.accounts.sol_vault.lamports();
+        let rent = Rent::get()?.minimum_balance(0);
+        let committed = rent + curve.real_sol_reserves + curve.platform_fees_pending
+            + curve.prize_pool_fees_pending + curve.reserves_fees_pending;
+        let backed = lamports.saturating_sub(committed);
+
+        let previous_pending = curve.creator_fees_pending;
+        curve.creator_fees_pending = previous_pending.min(backed);
+
+        emit!(CreatorFeesReconciled {
+            mint: curve.mint,
+            previous_pending,
+            reconciled_pending: curve.creator_fees_pending,
+            shortfall: committed.saturating_sub(lamports),
+        });
+
+        Ok(())
+    }
+
+    /// Quote a buy of `sol_amount` exactly as `buy` would fill it. Read-only, for simulation.
+    /// Pass the trader's referrer to quote its cut of the platform fee, and the trader's stats
+    /// and stake to quote their fee tier discount.
+    pub fn quote_buy(ctx: Context<QuoteTrade>, sol_amount: u64) -> Result<BuyQuote> {
+        let curve = &ctx.accounts.bonding_curve;
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::Buy)?;
+        require!(!curve.is_graduated, BondingCurveError::TokenGraduated);
+        require!(sol_amount > 0, BondingCurveError::InvalidAmount);
+
+        let fee_tier = trader_fee_tier(
+            &ctx.accounts.config,
+            ctx.accounts.user_stats.as_deref(),
+            ctx.accounts.stake_position.as_deref(),
+            Clock::get()?.unix_timestamp,
+        );
+        let schedule = ctx
+            .accounts
+            .config
+            .fee_schedule(curve.is_graduated)
+            .with_platform_discount(fee_tier.map_or(0, |tier| tier.platform_discount_bps));
+        let mut fill = curve.fill_buy(&schedule, sol_amount)?;
+        quote_referral(ctx.accounts, &mut fill.fees)?;
+        let mut after = (**curve).clone();
+        after.real_sol_reserves += fill.sol_to_curve;
+        after.real_token_reserves -= fill.tokens_out;
+        after.tokens_sold += fill.tokens_out;
+
+        Ok(BuyQuote {
+            sol_in: fill.sol_in,
+            sol_refunded: sol_amount - fill.sol_in,
+            tokens_out: fill.tokens_out,
+            price_impact_bps: price_impact_bps(curve.spot_price_q64(), after.spot_price_q64()),
+            price_after_q64: after.spot_price_q64(),
+            graduation_progress_bps: after.graduation_progress_bps(),
+            completes_curve: fill.completes_curve,
+            fees: fill.fees,
+            fee_tier,
+        })
+    }
+
+    /// Quote a sale of `token_amount` exactly as `sell` would fill it. Read-only, for simulation.
+    /// Pass the trader's referrer to quote its cut of the platform fee, and the trader's stats
+    /// and stake to quote their fee tier discount.
+    pub fn quote_sell(ctx: Context<QuoteTrade>, token_amount: u64) -> Result<SellQuote> {
+        let curve = &ctx.accounts.bonding_curve;
+        require_not_paused(&ctx.accounts.config, curve, PausableInstruction::Sell)?;
+        require!(!curve.is_graduated, BondingCurveError::TokenGraduated);
+        require!(token_amount > 0, BondingCurveError::InvalidAmount);
+
+        let fee_tier = trader_fee_tier(
+            &ctx.accounts.config,
+            ctx.accounts.user_stats.as_deref(),
+            ctx.accounts.stake_position.as_deref(),
+            Clock::get()?.unix_timestamp,
+        );
+        let schedule = ctx
+            .accounts
+            .config
+            .fee_schedule(curve.is_graduated)
+            .with_platform_discount(fee_tier.map_or(0, |tier| tier.platform_discount_bps));
+        let mut fill = curve.fill_sell(&schedule, token_amount)?;
+        quote_referral(ctx.accounts, &mut fill.fees)?;
+        let mut after = (**curve).clone();
+        after.real_sol_reserves -= fill.sol_out;
+        after.real_token_reserves += token_amount;
+        after.tokens_sold -= token_amount;
+
+        Ok(SellQuote {
+            sol_out: fill.sol_to_seller,
+            price_impact_bps: price_impact_bps(curve.spot_price_q64(), after.spot_price_q64()),
+            price_after_q64: after.spot_price_q64(),
+            graduation_progress_bps: after.graduation_progress_bps(),
+            fees: fill.fees,
+            fee_tier,
+        })
+    }
+
+    /// Current price, reserves and graduation progress of a curve. Read-only, for simulation.
+    pub fn get_curve_state(ctx: Context<QuoteCurve>) -> Result<CurveState> {
+        let curve = &ctx.accounts.bonding_curve;
+        Ok(CurveState {
+            mint: curve.mint,
+            spot_price_q64: curve.spot_price_q64(),
+            virtual_sol_reserves: curve.virtual_sol_reserves,
+            virtual_token_reserves: curve.virtual_token_reserves,
+            real_sol_reserves: curve.real_sol_reserves,
+            real_token_reserves: curve.real_token_reserves,
+            tokens_sold: curve.tokens_sold,
+            graduation_threshold: curve.graduation_threshold,
+            graduation_progress_bps: curve.graduation_progress_bps(),
+            sol_to_complete: if curve.is_graduated { 0 } else { curve.sol_to_complete()? },
+            is_graduated: curve.is_graduated,
+            paused: ctx.accounts.config.paused || curve.paused,
+            price_cumulative: curve.price_cumulative,
+            last_update_slot: curve.last_update_slot,
+        })
+    }
+
+    /// Report drift between a curve's accounting and its actual SOL and token balances.
+    /// Permissionless and read-only; the result is also returned for simulation.
+    pub fn sync_reserves(ctx: Context<SyncReserves>) -> Result<ReserveDrift> {
+        let drift = measure_drift(
+            &ctx.accounts.bonding_curve,
+            &ctx.accounts.sol_vault,
+            &ctx.accounts.curve_token_account,
+        )?;
+
+        if drift.has_drift() {
+            emit!(ReserveDriftDetected {
+                mint: ctx.accounts.bonding_curve.mint,
+                drift: drift.clone(),
+            });
+        }
+
+        Ok(drift)
+    }
+
+    /// Sweep stray SOL and tokens above a curve's accounted reserves to the reserves treasury
+    pub fn skim(ctx: Context<Skim>) -> Result<()> {
+        let curve = &ctx.accounts.bonding_curve;
+        let drift = measure_drift(curve, &ctx.accounts.sol_vault, &ctx.accounts.curve_token_account)?;
+
+        let sol_vault_excess = drift.sol_vault_excess();
+        let token_excess = drift.token_excess();
+        require!(
+            sol_vault_excess > 0 || drift.curve_account_excess > 0 || token_excess > 0,
+            BondingCurveError::NothingToSkim
+        );
+
+        let reserves_wallet = ctx.accounts.reserves_wallet.to_account_info();
+        transfer_from_sol_vault(
+            curve,
+            &ctx.accounts.sol_vault,
+            reserves_wallet.clone(),
+            &ctx.accounts.system_program,
+            sol_vault_excess,
+        )?;
+        transfer_lamports(&curve.to_account_info(), &reserves_wallet, drift.curve_account_excess)?;
+
+        if token_excess > 0 {
+            let seeds = &[b"bonding_curve", curve.mint.as_ref(), &[curve.bump]];
+            let signer = &[&seeds[..]];
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.curve_token_account.to_account_info(),
+                to: ctx.accounts.reserves_token_account.to_account_info(),
+                authority: curve.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            token::transfer(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer), token_excess)?;
+        }
+
+        emit!(ReservesSkimmed {
+            mint: curve.mint,
+            recipient: ctx.accounts.reserves_wallet.key(),
+            sol_amount: sol_vault_excess + drift.curve_account_excess,
+            token_amount: token_excess,
+        });
+
+        Ok(())
+    }
+
+    /// Create a curve's price observation ring buffer. Permissionless; the payer covers rent.
+    pub fn initialize_price_observations(ctx: Context<InitializePriceObservations>) -> Result<()> {
+        let curve = &mut ctx.accounts.bonding_curve;
+        curve.update_price_accumulator(Clock::get()?.slot);
+
+        let observations = &mut ctx.accounts.price_observations;
+        observations.mint = curve.mint;
+        observations.head = 0;
+        observations.count = 0;
+        observations.observations = [PriceObservation::default(); PriceObservations::CAPACITY];
+        observations.bump = ctx.bumps.price_observations;
+        observations.record(curve);
+
+        Ok(())
+    }
+
+    /// Record the curve's current price accumulator without trading. Permissionless.
+    pub fn record_price_observation(ctx: Context<RecordPriceObservation>) -> Result<()> {
+        let curve = &mut ctx.accounts.bonding_curve;
+        curve.update_price_accumulator(Clock::get()?.slot);
+        ctx.accounts.price_observations.record(curve);
+        Ok(())
+    }
+}
+
+/// Compare a curve's accounted reserves with what its accounts actually hold
+fn measure_drift(
+    curve: &Account<BondingCurve>,
+    sol_vault: &SystemAccount,
+    curve_token_account: &TokenAccount,
+) -> Result<ReserveDrift> {
+    let rent = Rent::get()?;
+    let curve_info = curve.to_account_info();
+    Ok(ReserveDrift {
+        sol_expected: rent.minimum_balance(0) + curve.real_sol_reserves + curve.total_fees_pending(),
+        sol_actual: sol_vault.lamports(),
+        curve_account_excess: curve_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(curve_info.data_len())),
+        tokens_expected: curve.real_token_reserves,
+        tokens_actual: curve_token_account.amount,
+    })
+}
+
+/// Reject an instruction if the program or the curve is paused and it is not allowlisted
+fn require_not_paused(config: &Config, curve: &BondingCurve, instruction: PausableInstruction) -> Result<()> {
+    if config.paused || curve.paused {
+        require!(config.pause_allowlist & instruction.bit() != 0, BondingCurveError::Paused);
+    }
+    Ok(())
+}
+
+/// Decode a curve account written with an older layout or version and carry it over to the current one
+fn upgrade_curve_data(data: &[u8]) -> Result<(BondingCurve, u8)> {
+    require!(
+        data.len() >= 8 && data[..8] == BondingCurve::DISCRIMINATOR,
+        BondingCurveError::InvalidCurveAccount
+    );
+
+    let (mut curve, from_version) = if data.len() == 8 + BondingCurveV0::LEN {
+        let legacy = BondingCurveV0::deserialize(&mut &data[8..])?;
+        (BondingCurve::from_legacy(legacy), 0)
+    } else {
+        require!(
+            data.len() == 8 + BondingCurve::INIT_SPACE,
+            BondingCurveError::InvalidCurveAccount
+        );
+        let curve = BondingCurve::try_deserialize(&mut &data[..])?;
+        let from_version = curve.version;
+        require!(
+            from_version < BondingCurve::CURRENT_VERSION,
+            BondingCurveError::CurveAlreadyMigrated
+        );
+        (curve, from_version)
+    };
+
+    // Version 2 moved reserves into the SOL vault; the vault bump is set by the caller
+    // Version 3 measures the graduation threshold in real SOL raised instead of virtual + real
+    if from_version < 3 {
+        curve.graduation_threshold = curve
+            .graduation_threshold
+            .saturating_sub(curve.virtual_sol_reserves)
+            .min(curve.max_sol_raised());
+    }
+    curve.version = BondingCurve::CURRENT_VERSION;
+    Ok((curve, from_version))
+}
+
+// Lamport helpers
+/// Move lamports out of an account owned by this program
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    **from.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? += amount;
+    Ok(())
+}
+
+/// Enforce that the SOL vault holds rent + real SOL reserves + every pending fee
+fn assert_curve_solvent(curve: &BondingCurve, sol_vault: &SystemAccount) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(0);
+    let required = rent + curve.real_sol_reserves + curve.total_fees_pending();
+    require!(sol_vault.lamports() >= required, BondingCurveError::CurveInsolvent);
+    Ok(())
+}
+
+/// Move SOL out of a curve's system-owned SOL vault
+fn transfer_from_sol_vault<'info>(
+    curve: &BondingCurve,
+    sol_vault: &SystemAccount<'info>,
+    to: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let seeds = &[b"sol_vault", curve.mint.as_ref(), &[curve.sol_vault_bump]];
+    let signer = &[&seeds[..]];
+    let cpi_context = CpiContext::new_with_signer(
+        system_program.to_account_info(),
+        anchor_lang::system_program::Transfer {
+            from: sol_vault.to_account_info(),
+            to,
+        },
+        signer,
+    );
+    anchor_lang::system_program::transfer(cpi_context, amount)
+}
+
+fn record_referral(referrer: &mut Referrer, amount: u64, volume: u64) {
+    referrer.referred_volume += volume;
+    referrer.referred_trades += 1;
+    referrer.earnings_accrued += amount;
+}
+
+/// The best fee tier a trader qualifies for by their 30-day volume across all curves or
+/// the platform tokens they stake
+fn trader_fee_tier(
+    config: &Config,
+    user_stats: Option<&Account<UserStats>>,
+    stake_position: Option<&Account<StakePosition>>,
+    now: i64,
+) -> Option<FeeTier> {
+    let volume_30d = user_stats.map_or(0, |user_stats| user_stats.stats.volume_30d(now));
+    let staked = stake_position.map_or(0, |position| position.amount);
+    config.fee_tier(volume_30d, staked)
+}
+
+/// Carve the referrer's cut out of quoted fees, as `buy` and `sell` do
+fn quote_referral(accounts: &QuoteTrade, fees: &mut FeeSplit) -> Result<()> {
+    if let Some(referrer) = &accounts.referrer {
+        require!(referrer.wallet != accounts.trader.key(), BondingCurveError::SelfReferral);
+        fees.apply_referral(accounts.config.referral_share_bps);
+    }
+    Ok(())
+}
+
+fn init_fee_vault(vault: &mut Account<FeeVault>, bucket: FeeBucket, bump: u8) {
+    vault.bucket = bucket;
+    vault.total_swept = 0;
+    vault.total_claimed = 0;
+    vault.bump = bump;
+}
+
+// Math functions
+/// Real SOL a constant-product curve raises by selling `supply` tokens, ignoring fees
+fn max_sol_raised(virtual_sol_reserves: u64, virtual_token_reserves: u64, supply: u64) -> u64 {
+    if supply >= virtual_token_reserves {
+        return 0;
+    }
+    let k = (virtual_sol_reserves as u128) * (virtual_token_reserves as u128);
+    let sol_reserves = k / (virtual_token_reserves - supply) as u128;
+    u64::try_from(sol_reserves - virtual_sol_reserves as u128).unwrap_or(u64::MAX)
+}
+
+/// Relative price move between two Q64.64 prices, in basis points
+fn price_impact_bps(price_before: u128, price_after: u128) -> u64 {
+    if price_before == 0 {
+        return 0;
+    }
+    let impact = price_before.abs_diff(price_after).saturating_mul(10000) / price_before;
+    u64::try_from(impact).unwrap_or(u64::MAX)
+}
+
+fn calculate_buy_tokens(sol_in: u64, sol_reserves: u64, token_reserves: u64) -> Result<u64> {
+    require!(sol_reserves > 0 && token_reserves > 0, BondingCurveError::InvalidCalculation);
+    let k = (sol_reserves as u128) * (token_reserves as u128);
+    let new_sol_reserves = sol_reserves as u128 + sol_in as u128;
+    let new_token_reserves = k / new_sol_reserves;
+    let tokens_out = token_reserves - (new_token_reserves as u64);
+    
+    require!(tokens_out > 0, BondingCurveError::InvalidCalculation);
+    Ok(tokens_out)
+}
+
+fn calculate_sell_sol(token_in: u64, sol_reserves: u64, token_reserves: u64) -> Result<u64> {
+    require!(sol_reserves > 0 && token_reserves > 0, BondingCurveError::InvalidCalculation);
+    let k = (sol_reserves as u128) * (token_reserves as u128);
+    let new_token_reserves = token_reserves as u128 + token_in as u128;
+    let new_sol_reserves = k / new_token_reserves;
+    let sol_out = sol_reserves - (new_sol_reserves as u64);
+    
+    require!(sol_out > 0, BondingCurveError::InvalidCalculation);
+    Ok(sol_out)
+}
+
+// Account structs
 #[derive(Accounts)]
-pub struct Graduate<'info> {
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [b"fee_vault", FeeBucket::Platform.seed()],
+        bump
+    )]
+    pub platform_vault: Account<'info, FeeVault>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [b"fee_vault", FeeBucket::PrizePool.seed()],
+        bump
+    )]
+    pub prize_pool_vault: Account<'info, FeeVault>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [b"fee_vault", FeeBucket::Reserves.seed()],
+        bump
+    )]
+    pub reserves_vault: Account<'info, FeeVault>,
+
+    #[account(
+        constraint = bonding_curve_program.programdata_address()? == Some(program_data.key())
+            @ BondingCurveError::Unauthorized
+    )]
+    pub bonding_curve_program: Program<'info, BondingCurveProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ BondingCurveError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminOnly<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PauserOnly<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.is_pauser(&pauser.key()) @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub pauser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCurvePaused<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.is_pauser(&pauser.key()) @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
     #[account(
         mut,
         seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
         bump = bonding_curve.bump
     )]
     pub bonding_curve: Account<'info, BondingCurve>,
+
+    pub pauser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.pending_admin == pending_admin.key() @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub pending_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueConfigChange<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + PendingConfigChange::INIT_SPACE,
+        seeds = [b"config_change", config.config_change_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingConfigChange>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfigChange<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"config_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump,
+        close = proposer
+    )]
+    pub pending_change: Account<'info, PendingConfigChange>,
+
+    /// CHECK: Receives the pending change's rent, bound to the proposer
+    #[account(mut, address = pending_change.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelConfigChange<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"config_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump,
+        close = proposer
+    )]
+    pub pending_change: Account<'info, PendingConfigChange>,
+
+    /// CHECK: Receives the pending change's rent, bound to the proposer
+    #[account(mut, address = pending_change.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+
+    pub canceller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCurve<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + BondingCurve::INIT_SPACE,
+        seeds = [b"bonding_curve", mint.key().as_ref()],
+        bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
     
-    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = bonding_curve
+    )]
+    pub curve_token_account: Account<'info, TokenAccount>,
     
-    /// CHECK: Platform wallet for authorization check
-    pub platform_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint.key().as_ref()],
+        bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+    
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct BuyTokens<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+    
+    #[account(address = bonding_curve.mint)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = bonding_curve
+    )]
+    pub curve_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer
+    )]
+    pub buyer_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"referrer", referrer.wallet.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    
+    /// Optional: sampled into the curve's observation ring buffer when passed
+    #[account(
+        mut,
+        seeds = [b"price_observations", bonding_curve.mint.as_ref()],
+        bump = price_observations.bump
+    )]
+    pub price_observations: Option<Account<'info, PriceObservations>>,
+    
+    /// Optional: the buyer's stats for this token
+    #[account(
+        mut,
+        seeds = [b"user_stats", buyer.key().as_ref(), bonding_curve.mint.as_ref()],
+        bump = user_mint_stats.bump
+    )]
+    pub user_mint_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the buyer's stats across all tokens
+    #[account(
+        mut,
+        seeds = [b"user_stats", buyer.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the buyer's platform token stake, for holder fee tiers
+    #[account(
+        seeds = [b"stake", buyer.key().as_ref()],
+        bump = stake_position.bump,
+        seeds::program = staking::ID,
+        constraint = stake_position.stake_mint == config.platform_token_mint
+            @ BondingCurveError::InvalidStakeMint
+    )]
+    pub stake_position: Option<Box<Account<'info, StakePosition>>>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SellTokens<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+    
+    #[account(
+        mut,
+        associated_token::mint = bonding_curve.mint,
+        associated_token::authority = bonding_curve
+    )]
+    pub curve_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        associated_token::mint = bonding_curve.mint,
+        associated_token::authority = seller
+    )]
+    pub seller_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"referrer", referrer.wallet.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    
+    /// Optional: sampled into the curve's observation ring buffer when passed
+    #[account(
+        mut,
+        seeds = [b"price_observations", bonding_curve.mint.as_ref()],
+        bump = price_observations.bump
+    )]
+    pub price_observations: Option<Account<'info, PriceObservations>>,
+    
+    /// Optional: the seller's stats for this token
+    #[account(
+        mut,
+        seeds = [b"user_stats", seller.key().as_ref(), bonding_curve.mint.as_ref()],
+        bump = user_mint_stats.bump
+    )]
+    pub user_mint_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the seller's stats across all tokens
+    #[account(
+        mut,
+        seeds = [b"user_stats", seller.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the seller's platform token stake, for holder fee tiers
+    #[account(
+        seeds = [b"stake", seller.key().as_ref()],
+        bump = stake_position.bump,
+        seeds::program = staking::ID,
+        constraint = stake_position.stake_mint == config.platform_token_mint
+            @ BondingCurveError::InvalidStakeMint
+    )]
+    pub stake_position: Option<Box<Account<'info, StakePosition>>>,
+    
+    #[account(
+        mut,
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Graduate<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+    
+    /// Reserves fee vault the crank reward is paid from
+    #[account(
+        mut,
+        seeds = [b"fee_vault", FeeBucket::Reserves.seed()],
+        bump = reserves_vault.bump
+    )]
+    pub reserves_vault: Account<'info, FeeVault>,
+    
+    /// CHECK: Signs the fee wrapper CPI; registered as the wrapper config's graduation authority
+    #[account(seeds = [b"graduation_authority"], bump)]
+    pub graduation_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee wrapper config, deserialized by the fee wrapper program
+    #[account(seeds = [b"wrapper_config"], bump, seeds::program = fee_wrapper_program.key())]
+    pub wrapper_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Token's fee wrapper, created by the fee wrapper program if it does not exist yet
+    #[account(
+        mut,
+        seeds = [b"wrapper", bonding_curve.mint.as_ref()],
+        bump,
+        seeds::program = fee_wrapper_program.key()
+    )]
+    pub wrapper: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    
+    pub fee_wrapper_program: Program<'info, FeeWrapperProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimCreatorFees<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+    
+    #[account(
+        mut,
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+    
+    #[account(mut, address = bonding_curve.creator)]
+    pub creator: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + Referrer::INIT_SPACE,
+        seeds = [b"referrer", wallet.key().as_ref()],
+        bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Option<Pubkey>)]
+pub struct InitializeUserStats<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [b"user_stats".as_ref(), user.key().as_ref(), mint.as_ref().map_or(&[][..], |mint| mint.as_ref())],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralEarnings<'info> {
+    #[account(
+        mut,
+        seeds = [b"referrer", wallet.key().as_ref()],
+        bump = referrer.bump,
+        has_one = wallet @ BondingCurveError::Unauthorized
+    )]
+    pub referrer: Account<'info, Referrer>,
+
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateCurve<'info> {
+    /// CHECK: Deserialized by hand since it may still use the original layout
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", mint.key().as_ref()],
+        bump
+    )]
+    pub bonding_curve: UncheckedAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint.key().as_ref()],
+        bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePriceObservations<'info> {
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PriceObservations::INIT_SPACE,
+        seeds = [b"price_observations", bonding_curve.mint.as_ref()],
+        bump
+    )]
+    pub price_observations: Account<'info, PriceObservations>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordPriceObservation<'info> {
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(
+        mut,
+        seeds = [b"price_observations", bonding_curve.mint.as_ref()],
+        bump = price_observations.bump
+    )]
+    pub price_observations: Account<'info, PriceObservations>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteCurve<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteTrade<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    /// CHECK: The wallet being quoted; only its key is used, to find its stats and stake
+    pub trader: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"referrer", referrer.wallet.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+
+    /// Optional: the trader's stats across all tokens, for volume fee tiers
+    #[account(
+        seeds = [b"user_stats", trader.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Option<Box<Account<'info, UserStats>>>,
+
+    /// Optional: the trader's platform token stake, for holder fee tiers
+    #[account(
+        seeds = [b"stake", trader.key().as_ref()],
+        bump = stake_position.bump,
+        seeds::program = staking::ID,
+        constraint = stake_position.stake_mint == config.platform_token_mint
+            @ BondingCurveError::InvalidStakeMint
+    )]
+    pub stake_position: Option<Box<Account<'info, StakePosition>>>,
+}
+
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    #[account(
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    #[account(
+        associated_token::mint = bonding_curve.mint,
+        associated_token::authority = bonding_curve
+    )]
+    pub curve_token_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ BondingCurveError::Unauthorized,
+        has_one = reserves_wallet @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(address = bonding_curve.mint)]
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = bonding_curve
+    )]
+    pub curve_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Reserves treasury, bound to the config
+    #[account(mut)]
+    pub reserves_wallet: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = mint,
+        associated_token::authority = reserves_wallet
+    )]
+    pub reserves_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileCreatorFees<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ BondingCurveError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
+        bump = bonding_curve.bump
+    )]
+    pub bonding_curve: Account<'info, BondingCurve>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", FeeBucket::Platform.seed()],
+        bump = platform_vault.bump
+    )]
+    pub platform_vault: Account<'info, FeeVault>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", FeeBucket::PrizePool.seed()],
+        bump = prize_pool_vault.bump
+    )]
+    pub prize_pool_vault: Account<'info, FeeVault>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", FeeBucket::Reserves.seed()],
+        bump = reserves_vault.bump
+    )]
+    pub reserves_vault: Account<'info, FeeVault>,
+
+    #[account(
+        mut,
+        seeds = [b"sol_vault", bonding_curve.mint.as_ref()],
+        bump = bonding_curve.sol_vault_bump
+    )]
+    pub sol_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bucket: FeeBucket)]
+pub struct ClaimFees<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_vault", bucket.seed()],
+        bump = fee_vault.bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    /// CHECK: Fee recipient, bound to the config
+    #[account(mut, address = config.recipient(bucket) @ BondingCurveError::Unauthorized)]
+    pub recipient: UncheckedAccount<'info>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct BondingCurve {
+    pub version: u8,                    // Account layout version
+    pub mint: Pubkey,                    // Token mint address
+    pub creator: Pubkey,                 // Token creator
+    pub virtual_sol_reserves: u64,      // Virtual SOL for price calculation
+    pub virtual_token_reserves: u64,    // Virtual tokens for price calculation
+    pub real_sol_reserves: u64,         // Actual SOL held by curve
+    pub real_token_reserves: u64,       // Actual tokens held by curve
+    pub tokens_sold: u64,               // Total tokens sold
+    pub is_graduated: bool,             // Whether token has graduated
+    pub graduation_threshold: u64,      // Real SOL to raise before graduating
+    pub total_fees_collected: u64,     // Total fees collected
+    pub creator_fees_pending: u64,     // Creator fees available to claim
+    pub platform_fees_pending: u64,    // Platform fees awaiting sweep
+    pub prize_pool_fees_pending: u64,  // Prize pool fees awaiting sweep
+    pub reserves_fees_pending: u64,    // Reserves fees awaiting sweep
+    pub paused: bool,                   // Whether trading on this curve is paused
+    pub bump: u8,                       // PDA bump
+    pub sol_vault_bump: u8,             // Bump of the SOL vault holding reserves and pending fees
+    pub graduation_completed: bool,     // Whether the graduate crank has run
+    pub price_cumulative: u128,         // Sum of Q64.64 spot price x slots elapsed, wrapping
+    pub last_update_slot: u64,          // Slot the price accumulator was last advanced to
+    pub reserved: [u8; BondingCurve::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl BondingCurve {
+    pub const CURRENT_VERSION: u8 = 3;
+    pub const RESERVED_BYTES: usize = 38;
+
+    /// Carry an original-layout curve over to the current layout.
+    /// Platform, prize pool and reserves fees were pushed at trade time, so nothing is pending.
+    pub fn from_legacy(legacy: BondingCurveV0) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            mint: legacy.mint,
+            creator: legacy.creator,
+            virtual_sol_reserves: legacy.virtual_sol_reserves,
+            virtual_token_reserves: legacy.virtual_token_reserves,
+            real_sol_reserves: legacy.real_sol_reserves,
+            real_token_reserves: legacy.real_token_reserves,
+            tokens_sold: legacy.tokens_sold,
+            is_graduated: legacy.is_graduated,
+            graduation_threshold: legacy.graduation_threshold,
+            total_fees_collected: legacy.total_fees_collected,
+            creator_fees_pending: legacy.creator_fees_pending,
+            platform_fees_pending: 0,
+            prize_pool_fees_pending: 0,
+            reserves_fees_pending: 0,
+            paused: false,
+            bump: legacy.bump,
+            sol_vault_bump: 0,
+            graduation_completed: false,
+            price_cumulative: 0,
+            last_update_slot: 0,
+            reserved: [0; Self::RESERVED_BYTES],
+        }
+    }
+
+    pub fn accrue_fees(&mut self, fees: &FeeSplit) {
+        self.total_fees_collected += fees.total();
+        self.creator_fees_pending += fees.creator;
+        self.platform_fees_pending += fees.platform;
+        self.prize_pool_fees_pending += fees.prize_pool;
+        self.reserves_fees_pending += fees.reserves;
+    }
+
+    /// Spot price in lamports per token base unit, as a Q64.64 fixed-point number
+    pub fn spot_price_q64(&self) -> u128 {
+        let sol_reserves = (self.virtual_sol_reserves + self.real_sol_reserves) as u128;
+        let token_reserves = (self.virtual_token_reserves - self.tokens_sold) as u128;
+        (sol_reserves << 64) / token_reserves
+    }
+
+    /// Advance the price accumulator to `slot` at the current spot price.
+    /// Must run before a trade moves the reserves so each price is weighted by how long it held.
+    pub fn update_price_accumulator(&mut self, slot: u64) {
+        // Curves created before the accumulator existed start accumulating from their first update
+        if self.last_update_slot > 0 && slot > self.last_update_slot {
+            let elapsed = (slot - self.last_update_slot) as u128;
+            self.price_cumulative = self
+                .price_cumulative
+                .wrapping_add(self.spot_price_q64().wrapping_mul(elapsed));
+        }
+        self.last_update_slot = self.last_update_slot.max(slot);
+    }
+
+    /// Net SOL a buy can still add before the curve graduates or sells out
+    pub fn sol_to_complete(&self) -> Result<u64> {
+        let to_threshold = self.graduation_threshold.saturating_sub(self.real_sol_reserves);
+
+        let sol_reserves = (self.virtual_sol_reserves + self.real_sol_reserves) as u128;
+        let token_reserves = (self.virtual_token_reserves - self.tokens_sold) as u128;
+        let final_token_reserves = token_reserves
+            .checked_sub(self.real_token_reserves as u128)
+            .filter(|reserves| *reserves > 0)
+            .ok_or(BondingCurveError::InvalidCalculation)?;
+        let final_sol_reserves = (sol_reserves * token_reserves).div_ceil(final_token_reserves);
+        let to_sell_out = u64::try_from(final_sol_reserves - sol_reserves).unwrap_or(u64::MAX);
+
+        Ok(to_threshold.min(to_sell_out))
+    }
+
+    /// Size a buy of `sol_amount` against the curve. A buy that would overshoot graduation is
+    /// scaled down so its net SOL lands on `sol_to_complete`, with its fees scaled alike.
+    pub fn fill_buy(&self, schedule: &FeeSchedule, sol_amount: u64) -> Result<BuyFill> {
+        let remaining = self.sol_to_complete()?;
+
+        let mut sol_in = sol_amount;
+        let mut fees = schedule.split(sol_in);
+        let mut sol_to_curve = sol_in - fees.total();
+        let completes_curve = sol_to_curve >= remaining;
+        if sol_to_curve > remaining {
+            sol_in = u64::try_from(
+                (sol_amount as u128 * remaining as u128).div_ceil(sol_to_curve as u128),
+            )
+            .map_err(|_| BondingCurveError::InvalidCalculation)?;
+            fees = schedule.split(sol_in);
+            sol_to_curve = sol_in - fees.total();
+        }
+        require!(sol_to_curve > 0, BondingCurveError::InvalidAmount);
+
+        let tokens_out = calculate_buy_tokens(
+            sol_to_curve,
+            self.virtual_sol_reserves + self.real_sol_reserves,
+            self.virtual_token_reserves - self.tokens_sold,
+        )?
+        .min(self.real_token_reserves);
+
+        Ok(BuyFill {
+            sol_in,
+            sol_to_curve,
+            tokens_out,
+            fees,
+            completes_curve,
+        })
+    }
+
+    /// Size a sale of `token_amount` against the curve, with fees taken out of the SOL side
+    pub fn fill_sell(&self, schedule: &FeeSchedule, token_amount: u64) -> Result<SellFill> {
+        let sol_out = calculate_sell_sol(
+            token_amount,
+            self.virtual_sol_reserves + self.real_sol_reserves,
+            self.virtual_token_reserves - self.tokens_sold,
+        )?;
+        require!(sol_out <= self.real_sol_reserves, BondingCurveError::InsufficientSol);
+
+        let fees = schedule.split(sol_out);
+        Ok(SellFill {
+            sol_out,
+            sol_to_seller: sol_out - fees.total(),
+            fees,
+        })
+    }
+
+    /// Graduate a curve that has already raised its graduation threshold, as happens when
+    /// migration lowers the threshold below what the curve holds. Returns whether it graduated.
+    pub fn graduate_if_threshold_reached(&mut self) -> bool {
+        if self.is_graduated || self.real_sol_reserves < self.graduation_threshold {
+            return false;
+        }
+        self.is_graduated = true;
+        true
+    }
+
+    /// Real SOL raised as a share of the graduation threshold, capped at 100%
+    pub fn graduation_progress_bps(&self) -> u16 {
+        if self.is_graduated || self.graduation_threshold == 0 {
+            return 10000;
+        }
+        let progress = self.real_sol_reserves as u128 * 10000 / self.graduation_threshold as u128;
+        progress.min(10000) as u16
+    }
+
+    /// Real SOL raised once the curve's entire supply has been sold
+    pub fn max_sol_raised(&self) -> u64 {
+        max_sol_raised(
+            self.virtual_sol_reserves,
+            self.virtual_token_reserves,
+            self.tokens_sold + self.real_token_reserves,
+        )
+    }
+
+    pub fn total_fees_pending(&self) -> u64 {
+        self.creator_fees_pending
+            + self.platform_fees_pending
+            + self.prize_pool_fees_pending
+            + self.reserves_fees_pending
+    }
+
+    /// Cap pending fees at `backing`, the vault lamports above rent and real SOL reserves.
+    /// Creator fees are cut first, since that's the bucket that was double-counted.
+    /// Returns the total written off.
+    pub fn cap_pending_fees(&mut self, backing: u64) -> u64 {
+        let mut remaining = backing;
+        let mut written_off = 0;
+        for pending in [
+            &mut self.platform_fees_pending,
+            &mut self.prize_pool_fees_pending,
+            &mut self.reserves_fees_pending,
+            &mut self.creator_fees_pending,
+        ] {
+            let kept = (*pending).min(remaining);
+            written_off += *pending - kept;
+            remaining -= kept;
+            *pending = kept;
+        }
+        written_off
+    }
+}
+
+/// Ring buffer of a curve's price accumulator samples, for on-chain TWAP consumers
+#[account]
+#[derive(InitSpace)]
+pub struct PriceObservations {
+    pub mint: Pubkey,
+    pub head: u8,                         // Index of the most recent observation
+    pub count: u8,                        // Number of populated observations
+    pub observations: [PriceObservation; PriceObservations::CAPACITY],
+    pub bump: u8,
+}
+
+impl PriceObservations {
+    pub const CAPACITY: usize = 64;
+
+    /// Sample the curve's accumulator, at most once per slot
+    pub fn record(&mut self, curve: &BondingCurve) {
+        let observation = PriceObservation {
+            slot: curve.last_update_slot,
+            price_cumulative: curve.price_cumulative,
+        };
+        if self.count > 0 {
+            let latest = &mut self.observations[self.head as usize];
+            if latest.slot == observation.slot {
+                *latest = observation;
+                return;
+            }
+            self.head = ((self.head as usize + 1) % Self::CAPACITY) as u8;
+        }
+        self.observations[self.head as usize] = observation;
+        self.count = (self.count as usize + 1).min(Self::CAPACITY) as u8;
+    }
+
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        (self.count > 0).then(|| &self.observations[self.head as usize])
+    }
+
+    /// Newest observation recorded at or before `slot`
+    pub fn at_or_before(&self, slot: u64) -> Option<&PriceObservation> {
+        (0..self.count as usize)
+            .map(|age| &self.observations[(self.head as usize + Self::CAPACITY - age) % Self::CAPACITY])
+            .find(|observation| observation.slot <= slot)
+    }
+
+    /// Q64.64 time-weighted average price from the newest observation at least `window_slots`
+    /// older than `current` up to `current`
+    pub fn twap(&self, current: &PriceObservation, window_slots: u64) -> Option<u128> {
+        let start = self.at_or_before(current.slot.checked_sub(window_slots)?)?;
+        start.twap_to(current)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PriceObservation {
+    pub slot: u64,
+    pub price_cumulative: u128,
+}
+
+impl PriceObservation {
+    /// Q64.64 average price between this observation and a later one
+    pub fn twap_to(&self, later: &PriceObservation) -> Option<u128> {
+        let elapsed = later.slot.checked_sub(self.slot).filter(|elapsed| *elapsed > 0)?;
+        Some(later.price_cumulative.wrapping_sub(self.price_cumulative) / elapsed as u128)
+    }
+}
+
+/// The filled part of a buy, as sized by `BondingCurve::fill_buy`
+pub struct BuyFill {
+    pub sol_in: u64,                      // SOL taken from the buyer, fees included
+    pub sol_to_curve: u64,                // SOL added to the real reserves
+    pub tokens_out: u64,
+    pub fees: FeeSplit,
+    pub completes_curve: bool,            // Whether this buy graduates the curve
+}
+
+/// The filled part of a sale, as sized by `BondingCurve::fill_sell`
+pub struct SellFill {
+    pub sol_out: u64,                     // SOL taken out of the real reserves, fees included
+    pub sol_to_seller: u64,
+    pub fees: FeeSplit,
+}
+
+/// Returned by `quote_buy`. Prices are Q64.64 lamports per token base unit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct BuyQuote {
+    pub sol_in: u64,                      // SOL the buy would take, fees included
+    pub sol_refunded: u64,                // Part of the requested amount left unfilled
+    pub tokens_out: u64,
+    pub fees: FeeSplit,
+    pub price_impact_bps: u64,
+    pub price_after_q64: u128,
+    pub graduation_progress_bps: u16,     // After the trade
+    pub completes_curve: bool,
+    pub fee_tier: Option<FeeTier>,        // Fee tier discount applied to the platform fee
+}
+
+/// Returned by `quote_sell`. Prices are Q64.64 lamports per token base unit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct SellQuote {
+    pub sol_out: u64,                     // SOL the seller would receive, after fees
+    pub fees: FeeSplit,
+    pub price_impact_bps: u64,
+    pub price_after_q64: u128,
+    pub graduation_progress_bps: u16,     // After the trade
+    pub fee_tier: Option<FeeTier>,        // Fee tier discount applied to the platform fee
+}
+
+/// Returned by `get_curve_state`. Prices are Q64.64 lamports per token base unit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct CurveState {
+    pub mint: Pubkey,
+    pub spot_price_q64: u128,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub tokens_sold: u64,
+    pub graduation_threshold: u64,
+    pub graduation_progress_bps: u16,
+    pub sol_to_complete: u64,             // Net SOL still needed to graduate or sell out
+    pub is_graduated: bool,
+    pub paused: bool,                     // Globally or for this curve
+    pub price_cumulative: u128,
+    pub last_update_slot: u64,
+}
+
+/// Original, unversioned `BondingCurve` layout, kept for `migrate_curve`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BondingCurveV0 {
+    pub mint: Pubkey,
+    pub creator: Pubkey,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub tokens_sold: u64,
+    pub is_graduated: bool,
+    pub graduation_threshold: u64,
+    pub total_fees_collected: u64,
+    pub creator_fees_pending: u64,
+    pub bump: u8,
+}
+
+impl BondingCurveV0 {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 1;
+}
+
+/// Accounted vs actual balances of a curve, as reported by `sync_reserves`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct ReserveDrift {
+    pub sol_expected: u64,                // Vault rent + real SOL reserves + pending fees
+    pub sol_actual: u64,                  // SOL vault balance
+    pub curve_account_excess: u64,        // Lamports on the curve state account above rent
+    pub tokens_expected: u64,             // real_token_reserves
+    pub tokens_actual: u64,               // Curve token account balance
+}
+
+impl ReserveDrift {
+    pub fn sol_vault_excess(&self) -> u64 {
+        self.sol_actual.saturating_sub(self.sol_expected)
+    }
+
+    pub fn token_excess(&self) -> u64 {
+        self.tokens_actual.saturating_sub(self.tokens_expected)
+    }
+
+    pub fn has_drift(&self) -> bool {
+        self.sol_actual != self.sol_expected
+            || self.curve_account_excess > 0
+            || self.tokens_actual != self.tokens_expected
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,            // Default pubkey when no transfer is in progress
+    pub pauser: Pubkey,
+    pub fee_manager: Pubkey,
+    pub platform_wallet: Pubkey,          // Staking pool PDA; platform fees go to stakers
+    pub prize_pool_wallet: Pubkey,
+    pub reserves_wallet: Pubkey,          // Treasury reserves vault PDA
+    pub pre_graduation_fees: FeeSchedule,
+    pub post_graduation_fees: FeeSchedule,
+    pub referral_share_bps: u16,          // Share of the platform fee paid to referrers
+    pub config_delay_seconds: i64,        // Delay before a queued config change can execute
+    pub config_change_count: u64,
+    pub paused: bool,                     // Global pause
+    pub pause_allowlist: u8,              // PausableInstruction bits still allowed while paused
+    #[max_len(8)]
+    pub curve_presets: Vec<CurvePreset>,  // Curve parameters creators can launch with
+    pub min_graduation_threshold: u64,    // Bounds on a curve's graduation threshold, in real SOL
+    pub max_graduation_threshold: u64,
+    pub graduation_reward: u64,           // Paid from the reserves fee vault to whoever cranks `graduate`
+    pub platform_token_mint: Pubkey,      // Token whose stakers qualify for holder fee tiers
+    #[max_len(8)]
+    pub fee_tiers: Vec<FeeTier>,          // Platform fee discounts by 30-day volume or stake
+    pub bump: u8,
+    pub reserved: [u8; Config::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl Config {
+    pub const DEFAULT_REFERRAL_SHARE_BPS: u16 = 2000;
+    pub const DEFAULT_CONFIG_DELAY_SECONDS: i64 = 48 * 60 * 60;
+    pub const MIN_CONFIG_DELAY_SECONDS: i64 = 60 * 60;
+    pub const MAX_CONFIG_DELAY_SECONDS: i64 = 30 * 24 * 60 * 60;
+    pub const DEFAULT_MIN_GRADUATION_THRESHOLD: u64 = 10 * LAMPORTS_PER_SOL;
+    pub const DEFAULT_MAX_GRADUATION_THRESHOLD: u64 = 500 * LAMPORTS_PER_SOL;
+    pub const DEFAULT_GRADUATION_REWARD: u64 = LAMPORTS_PER_SOL / 100;
+    pub const MAX_GRADUATION_REWARD: u64 = LAMPORTS_PER_SOL;
+    pub const RESERVED_BYTES: usize = 128;
+
+    pub fn validate_graduation_threshold(&self, threshold: u64) -> Result<()> {
+        require!(
+            threshold >= self.min_graduation_threshold && threshold <= self.max_graduation_threshold,
+            BondingCurveError::InvalidGraduationThreshold
+        );
+        Ok(())
+    }
+
+    pub fn curve_preset(&self, id: u8) -> Result<&CurvePreset> {
+        self.curve_presets
+            .iter()
+            .find(|preset| preset.id == id && preset.enabled)
+            .ok_or_else(|| error!(BondingCurveError::UnknownCurvePreset))
+    }
+
+    pub fn is_pauser(&self, key: &Pubkey) -> bool {
+        *key == self.pauser || *key == self.admin
+    }
+
+    pub fn can_manage(&self, key: &Pubkey, change: &ConfigChange) -> bool {
+        *key == self.admin || (*key == self.fee_manager && change.fee_manager_allowed())
+    }
+
+    /// The qualifying fee tier with the largest discount
+    pub fn fee_tier(&self, volume_30d: u64, staked: u64) -> Option<FeeTier> {
+        FeeTier::best(&self.fee_tiers, volume_30d, staked)
+    }
+
+    pub fn fee_schedule(&self, is_graduated: bool) -> &FeeSchedule {
+        if is_graduated {
+            &self.post_graduation_fees
+        } else {
+            &self.pre_graduation_fees
+        }
+    }
+
+    pub fn recipient(&self, bucket: FeeBucket) -> Pubkey {
+        match bucket {
+            FeeBucket::Platform => self.platform_wallet,
+            FeeBucket::PrizePool => self.prize_pool_wallet,
+            FeeBucket::Reserves => self.reserves_wallet,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Pauser,
+    FeeManager,
+}
+
+/// Named set of curve parameters creators can initialize a curve with
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, PartialEq, Eq)]
+pub struct CurvePreset {
+    pub id: u8,
+    #[max_len(16)]
+    pub name: String,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub bonding_curve_supply: u64,
+    pub graduation_threshold: u64,      // Default real SOL to raise before graduating
+    pub enabled: bool,
+}
+
+impl CurvePreset {
+    pub const STANDARD: u8 = 0;
+    pub const LOW_CAP: u8 = 1;
+    pub const HIGH_CAP: u8 = 2;
+
+    pub const MAX_PRESETS: usize = 8;
+    pub const MAX_NAME_LEN: usize = 16;
+    pub const MIN_VIRTUAL_SOL_RESERVES: u64 = LAMPORTS_PER_SOL;
+    pub const MAX_VIRTUAL_SOL_RESERVES: u64 = 1_000 * LAMPORTS_PER_SOL;
+
+    pub fn defaults() -> Vec<CurvePreset> {
+        let tokens = 1_000_000_000; // 9 decimals
+        let preset = |id: u8, name: &str, virtual_sol: u64, threshold: u64| CurvePreset {
+            id,
+            name: name.to_string(),
+            virtual_sol_reserves: virtual_sol * LAMPORTS_PER_SOL,
+            virtual_token_reserves: 1_073_000_000 * tokens,
+            bonding_curve_supply: 800_000_000 * tokens,
+            graduation_threshold: threshold * LAMPORTS_PER_SOL,
+            enabled: true,
+        };
+        // Selling the full 800M supply raises ~88, ~44 and ~176 SOL respectively
+        vec![
+            preset(Self::STANDARD, "standard", 30, 85),
+            preset(Self::LOW_CAP, "low-cap", 15, 40),
+            preset(Self::HIGH_CAP, "high-cap", 60, 170),
+        ]
+    }
+
+    /// Real SOL raised once the preset's entire supply has been sold
+    pub fn max_sol_raised(&self) -> u64 {
+        max_sol_raised(
+            self.virtual_sol_reserves,
+            self.virtual_token_reserves,
+            self.bonding_curve_supply,
+        )
+    }
+
+    /// The curve supply must stay strictly below the virtual token reserves so
+    /// `virtual_token_reserves - tokens_sold` can never reach zero or underflow,
+    /// and the graduation threshold must be reachable by selling that supply.
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.name.len() <= Self::MAX_NAME_LEN,
+            BondingCurveError::InvalidCurveParameters
+        );
+        require!(
+            self.virtual_sol_reserves >= Self::MIN_VIRTUAL_SOL_RESERVES
+                && self.virtual_sol_reserves <= Self::MAX_VIRTUAL_SOL_RESERVES,
+            BondingCurveError::InvalidCurveParameters
+        );
+        require!(
+            self.bonding_curve_supply > 0
+                && self.bonding_curve_supply < self.virtual_token_reserves,
+            BondingCurveError::InvalidCurveParameters
+        );
+        require!(
+            self.graduation_threshold > 0 && self.graduation_threshold <= self.max_sol_raised(),
+            BondingCurveError::InvalidGraduationThreshold
+        );
+        Ok(())
+    }
 }
 
-#[derive(Accounts)]
-pub struct ClaimCreatorFees<'info> {
-    #[account(
-        mut,
-        seeds = [b"bonding_curve", bonding_curve.mint.key().as_ref()],
-        bump = bonding_curve.bump
-    )]
-    pub bonding_curve: Account<'info, BondingCurve>,
-    
-    #[account(mut, address = bonding_curve.creator)]
-    pub creator: Signer<'info>,
+/// Instructions that can be kept available while paused
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PausableInstruction {
+    Buy,
+    Sell,
+    Graduate,
+    ClaimCreatorFees,
+}
+
+impl PausableInstruction {
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    pub fn mask(instructions: &[PausableInstruction]) -> u8 {
+        instructions.iter().fold(0, |mask, instruction| mask | instruction.bit())
+    }
+}
+
+/// A config change that has to wait out the config delay before it applies
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    FeeRecipients {
+        platform_wallet: Pubkey,
+        prize_pool_wallet: Pubkey,
+        reserves_wallet: Pubkey,
+    },
+    FeeRates {
+        pre_graduation_fees: FeeSchedule,
+        post_graduation_fees: FeeSchedule,
+    },
+    ReferralShare {
+        referral_share_bps: u16,
+    },
+    ConfigDelay {
+        delay_seconds: i64,
+    },
+    /// Add a curve preset, or replace the one with the same ID
+    CurvePreset {
+        preset: CurvePreset,
+    },
+    GraduationThresholdBounds {
+        min_graduation_threshold: u64,
+        max_graduation_threshold: u64,
+    },
+    GraduationReward {
+        graduation_reward: u64,
+    },
+    /// Replace the fee tier table
+    FeeTiers {
+        platform_token_mint: Pubkey,
+        #[max_len(8)]
+        fee_tiers: Vec<FeeTier>,
+    },
+}
+
+impl ConfigChange {
+    pub fn fee_manager_allowed(&self) -> bool {
+        matches!(
+            self,
+            ConfigChange::FeeRates { .. }
+                | ConfigChange::ReferralShare { .. }
+                | ConfigChange::FeeTiers { .. }
+        )
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ConfigChange::FeeRecipients { .. } => {}
+            ConfigChange::FeeRates { pre_graduation_fees, post_graduation_fees } => {
+                pre_graduation_fees.validate()?;
+                post_graduation_fees.validate()?;
+            }
+            ConfigChange::ReferralShare { referral_share_bps } => {
+                require!(*referral_share_bps <= 10000, BondingCurveError::FeeTooHigh);
+            }
+            ConfigChange::ConfigDelay { delay_seconds } => {
+                require!(
+                    *delay_seconds >= Config::MIN_CONFIG_DELAY_SECONDS,
+                    BondingCurveError::ConfigDelayTooShort
+                );
+                require!(
+                    *delay_seconds <= Config::MAX_CONFIG_DELAY_SECONDS,
+                    BondingCurveError::ConfigDelayTooLong
+                );
+            }
+            ConfigChange::CurvePreset { preset } => preset.validate()?,
+            ConfigChange::GraduationThresholdBounds {
+                min_graduation_threshold,
+                max_graduation_threshold,
+            } => {
+                require!(
+                    *min_graduation_threshold > 0
+                        && min_graduation_threshold <= max_graduation_threshold,
+                    BondingCurveError::InvalidGraduationThreshold
+                );
+            }
+            ConfigChange::GraduationReward { graduation_reward } => {
+                require!(
+                    *graduation_reward <= Config::MAX_GRADUATION_REWARD,
+                    BondingCurveError::GraduationRewardTooHigh
+                );
+            }
+            ConfigChange::FeeTiers { fee_tiers, .. } => {
+                require!(FeeTier::is_valid_table(fee_tiers), BondingCurveError::InvalidFeeTier);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &mut Config) -> Result<()> {
+        match self.clone() {
+            ConfigChange::FeeRecipients { platform_wallet, prize_pool_wallet, reserves_wallet } => {
+                config.platform_wallet = platform_wallet;
+                config.prize_pool_wallet = prize_pool_wallet;
+                config.reserves_wallet = reserves_wallet;
+            }
+            ConfigChange::FeeRates { pre_graduation_fees, post_graduation_fees } => {
+                config.pre_graduation_fees = pre_graduation_fees;
+                config.post_graduation_fees = post_graduation_fees;
+            }
+            ConfigChange::ReferralShare { referral_share_bps } => {
+                config.referral_share_bps = referral_share_bps;
+            }
+            ConfigChange::ConfigDelay { delay_seconds } => {
+                config.config_delay_seconds = delay_seconds;
+            }
+            ConfigChange::CurvePreset { preset } => {
+                match config.curve_presets.iter_mut().find(|existing| existing.id == preset.id) {
+                    Some(existing) => *existing = preset,
+                    None => {
+                        require!(
+                            config.curve_presets.len() < CurvePreset::MAX_PRESETS,
+                            BondingCurveError::TooManyCurvePresets
+                        );
+                        config.curve_presets.push(preset);
+                    }
+                }
+            }
+            ConfigChange::GraduationThresholdBounds {
+                min_graduation_threshold,
+                max_graduation_threshold,
+            } => {
+                config.min_graduation_threshold = min_graduation_threshold;
+                config.max_graduation_threshold = max_graduation_threshold;
+            }
+            ConfigChange::GraduationReward { graduation_reward } => {
+                config.graduation_reward = graduation_reward;
+            }
+            ConfigChange::FeeTiers { platform_token_mint, fee_tiers } => {
+                config.platform_token_mint = platform_token_mint;
+                config.fee_tiers = fee_tiers;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[account]
-pub struct BondingCurve {
-    pub mint: Pubkey,                    // Token mint address
-    pub creator: Pubkey,                 // Token creator
-    pub virtual_sol_reserves: u64,      // Virtual SOL for price calculation
-    pub virtual_token_reserves: u64,    // Virtual tokens for price calculation
-    pub real_sol_reserves: u64,         // Actual SOL held by curve
-    pub real_token_reserves: u64,       // Actual tokens held by curve
-    pub tokens_sold: u64,               // Total tokens sold
-    pub is_graduated: bool,             // Whether token has graduated
-    pub graduation_threshold: u64,      // SOL threshold for graduation
-    pub total_fees_collected: u64,     // Total fees collected
-    pub creator_fees_pending: u64,     // Creator fees available to claim
-    pub bump: u8,                       // PDA bump
+#[derive(InitSpace)]
+pub struct PendingConfigChange {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub proposer: Pubkey,
+    pub queued_at: i64,
+    pub eta: i64,
+    pub bump: u8,
 }
 
-impl BondingCurve {
-    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 1;
+impl PendingConfigChange {
+    /// The delay has elapsed and the proposer still holds a role allowed to make the change
+    pub fn check_executable(&self, config: &Config, now: i64) -> Result<()> {
+        require!(now >= self.eta, BondingCurveError::ConfigChangeNotReady);
+        require!(
+            config.can_manage(&self.proposer, &self.change),
+            BondingCurveError::ProposerRoleRevoked
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeVault {
+    pub bucket: FeeBucket,
+    pub total_swept: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+/// Fee rates in basis points, applied to the SOL side of every buy and sell
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub platform_bps: u16,
+    pub creator_bps: u16,
+    pub prize_pool_bps: u16,
+    pub reserves_bps: u16,
+}
+
+impl FeeSchedule {
+    pub const MAX_TOTAL_BPS: u16 = 1000;
+
+    // Pre-graduation: 1% platform, 0.5% creator, 0.3% prize, 0.2% reserves
+    pub const PRE_GRADUATION: Self = Self {
+        platform_bps: 100,
+        creator_bps: 50,
+        prize_pool_bps: 30,
+        reserves_bps: 20,
+    };
+
+    // Post-graduation: 0.5% platform, 1% creator, 0.3% prize, 0.2% reserves
+    pub const POST_GRADUATION: Self = Self {
+        platform_bps: 50,
+        creator_bps: 100,
+        prize_pool_bps: 30,
+        reserves_bps: 20,
+    };
+
+    pub fn total_bps(&self) -> u32 {
+        self.platform_bps as u32
+            + self.creator_bps as u32
+            + self.prize_pool_bps as u32
+            + self.reserves_bps as u32
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.total_bps() <= Self::MAX_TOTAL_BPS as u32, BondingCurveError::FeeTooHigh);
+        Ok(())
+    }
+
+    /// The schedule with `discount_bps` of the platform fee waived
+    pub fn with_platform_discount(&self, discount_bps: u16) -> Self {
+        let waived = (self.platform_bps as u32 * discount_bps as u32 / 10000) as u16;
+        Self {
+            platform_bps: self.platform_bps - waived,
+            ..*self
+        }
+    }
+
+    pub fn split(&self, amount: u64) -> FeeSplit {
+        let fee = |bps: u16| (amount as u128 * bps as u128 / 10000) as u64;
+        FeeSplit {
+            platform: fee(self.platform_bps),
+            creator: fee(self.creator_bps),
+            prize_pool: fee(self.prize_pool_bps),
+            reserves: fee(self.reserves_bps),
+            referral: 0,
+        }
+    }
+}
+
+/// Fee amounts in lamports for a single trade
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct FeeSplit {
+    pub platform: u64,
+    pub creator: u64,
+    pub prize_pool: u64,
+    pub reserves: u64,
+    pub referral: u64,
+}
+
+impl FeeSplit {
+    pub fn total(&self) -> u64 {
+        self.platform + self.creator + self.prize_pool + self.reserves + self.referral
+    }
+
+    /// Carve the referrer's share out of the platform fee
+    pub fn apply_referral(&mut self, referral_share_bps: u16) {
+        self.referral = (self.platform as u128 * referral_share_bps as u128 / 10000) as u64;
+        self.platform -= self.referral;
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Referrer {
+    pub wallet: Pubkey,
+    pub referred_volume: u64,             // SOL volume of referred trades
+    pub referred_trades: u64,
+    pub earnings_accrued: u64,            // Lifetime referral earnings
+    pub earnings_claimed: u64,
+    pub bump: u8,
+}
+
+/// A wallet's trading on the curves, per token at `[b"user_stats", user, mint]` and across all
+/// tokens at `[b"user_stats", user]` (with `mint` left as the default pubkey)
+#[account]
+#[derive(InitSpace)]
+pub struct UserStats {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub stats: TradeStats,                // Volume counts SOL traded, fees included
+    pub bump: u8,
+    pub reserved: [u8; UserStats::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl UserStats {
+    pub const RESERVED_BYTES: usize = 64;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq)]
+pub enum FeeBucket {
+    Platform,
+    PrizePool,
+    Reserves,
+}
+
+impl FeeBucket {
+    pub fn seed(&self) -> &'static [u8] {
+        match self {
+            FeeBucket::Platform => b"platform",
+            FeeBucket::PrizePool => b"prize_pool",
+            FeeBucket::Reserves => b"reserves",
+        }
+    }
 }
 
 // Events
@@ -514,21 +2838,27 @@ impl BondingCurve {
 pub struct CurveInitialized {
     pub mint: Pubkey,
     pub creator: Pubkey,
+    pub preset_id: u8,
     pub virtual_sol_reserves: u64,
     pub virtual_token_reserves: u64,
     pub bonding_curve_supply: u64,
+    pub graduation_threshold: u64,
 }
 
 #[event]
 pub struct TokensPurchased {
     pub buyer: Pubkey,
     pub mint: Pubkey,
-    pub sol_amount: u64,
+    pub sol_amount: u64,                  // SOL actually spent, fees included
+    pub sol_refunded: u64,                // Part of the requested amount left unfilled
     pub tokens_received: u64,
     pub platform_fee: u64,
     pub creator_fee: u64,
     pub prize_pool_fee: u64,
     pub reserves_fee: u64,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    pub fee_tier: Option<FeeTier>,        // Fee tier discount applied to the platform fee
 }
 
 #[event]
@@ -537,14 +2867,20 @@ pub struct TokensSold {
     pub mint: Pubkey,
     pub token_amount: u64,
     pub sol_received: u64,
-    pub creator_fee: u64,
     pub platform_fee: u64,
+    pub creator_fee: u64,
+    pub prize_pool_fee: u64,
+    pub reserves_fee: u64,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    pub fee_tier: Option<FeeTier>,        // Fee tier discount applied to the platform fee
 }
 
 #[event]
 pub struct TokenGraduated {
     pub mint: Pubkey,
     pub total_sol_raised: u64,
+    pub graduation_threshold: u64,
     pub tokens_sold: u64,
 }
 
@@ -553,6 +2889,9 @@ pub struct TokenFullyGraduated {
     pub mint: Pubkey,
     pub final_sol_reserves: u64,
     pub remaining_tokens: u64,
+    pub cranker: Pubkey,
+    pub reward: u64,
+    pub wrapper: Pubkey,
 }
 
 #[event]
@@ -561,6 +2900,116 @@ pub struct CreatorFeesClaimed {
     pub amount: u64,
 }
 
+#[event]
+pub struct CreatorFeesReconciled {
+    pub mint: Pubkey,
+    pub previous_pending: u64,
+    pub reconciled_pending: u64,
+    pub shortfall: u64,
+}
+
+#[event]
+pub struct CurveMigrated {
+    pub mint: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub moved_to_vault: u64,
+    pub fees_written_off: u64,
+}
+
+#[event]
+pub struct ReserveDriftDetected {
+    pub mint: Pubkey,
+    pub drift: ReserveDrift,
+}
+
+#[event]
+pub struct ReservesSkimmed {
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+}
+
+#[event]
+pub struct ConfigInitialized {
+    pub admin: Pubkey,
+    pub platform_wallet: Pubkey,
+    pub prize_pool_wallet: Pubkey,
+    pub reserves_wallet: Pubkey,
+}
+
+#[event]
+pub struct RoleUpdated {
+    pub role: Role,
+    pub account: Pubkey,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub mint: Option<Pubkey>,             // None for the global pause
+    pub paused: bool,
+    pub allowlist: u8,
+}
+
+#[event]
+pub struct AdminTransferStarted {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct ConfigChangeQueued {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub proposer: Pubkey,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ConfigChangeExecuted {
+    pub id: u64,
+    pub change: ConfigChange,
+}
+
+#[event]
+pub struct ConfigChangeCancelled {
+    pub id: u64,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct ReferrerRegistered {
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct ReferralEarningsClaimed {
+    pub wallet: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FeesSwept {
+    pub mint: Pubkey,
+    pub platform_amount: u64,
+    pub prize_pool_amount: u64,
+    pub reserves_amount: u64,
+}
+
+#[event]
+pub struct FeesClaimed {
+    pub bucket: FeeBucket,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
 #[error_code]
 pub enum BondingCurveError {
     #[msg("Token has already graduated")]
@@ -583,4 +3032,390 @@ pub enum BondingCurveError {
     InvalidCalculation,
     #[msg("No fees available to claim")]
     NoFeesToClaim,
-}
\ No newline at end of file
+    #[msg("Curve lamports do not cover rent, reserves and pending fees")]
+    CurveInsolvent,
+    #[msg("Traders cannot refer themselves")]
+    SelfReferral,
+    #[msg("Role cannot be assigned directly")]
+    InvalidRole,
+    #[msg("Config change delay has not elapsed")]
+    ConfigChangeNotReady,
+    #[msg("Config delay is below the minimum")]
+    ConfigDelayTooShort,
+    #[msg("Config delay is above the maximum")]
+    ConfigDelayTooLong,
+    #[msg("Proposer no longer holds a role allowed to make this change")]
+    ProposerRoleRevoked,
+    #[msg("Instruction is paused")]
+    Paused,
+    #[msg("Account is not a bonding curve")]
+    InvalidCurveAccount,
+    #[msg("Curve already uses the current layout")]
+    CurveAlreadyMigrated,
+    #[msg("No excess balance to skim")]
+    NothingToSkim,
+    #[msg("Invalid curve parameters")]
+    InvalidCurveParameters,
+    #[msg("Unknown or disabled curve preset")]
+    UnknownCurvePreset,
+    #[msg("Too many curve presets")]
+    TooManyCurvePresets,
+    #[msg("Graduation threshold is out of bounds or unreachable")]
+    InvalidGraduationThreshold,
+    #[msg("Graduation reward is above the maximum")]
+    GraduationRewardTooHigh,
+    #[msg("Curve has already been graduated")]
+    AlreadyGraduated,
+    #[msg("Invalid fee tier")]
+    InvalidFeeTier,
+    #[msg("Stake is not in the platform token")]
+    InvalidStakeMint,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_curve() -> BondingCurveV0 {
+        BondingCurveV0 {
+            mint: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            virtual_sol_reserves: 30 * LAMPORTS_PER_SOL,
+            virtual_token_reserves: 1_073_000_000_000_000_000,
+            real_sol_reserves: 12 * LAMPORTS_PER_SOL,
+            real_token_reserves: 650_000_000_000_000_000,
+            tokens_sold: 150_000_000_000_000_000,
+            is_graduated: false,
+            graduation_threshold: 326 * LAMPORTS_PER_SOL,
+            total_fees_collected: LAMPORTS_PER_SOL / 5,
+            creator_fees_pending: LAMPORTS_PER_SOL / 20,
+            bump: 254,
+        }
+    }
+
+    fn legacy_account_data(legacy: &BondingCurveV0) -> Vec<u8> {
+        let mut data = BondingCurve::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn legacy_layout_matches_len() {
+        let data = legacy_account_data(&legacy_curve());
+        assert_eq!(data.len(), 8 + BondingCurveV0::LEN);
+    }
+
+    #[test]
+    fn migrates_legacy_curve_to_current_layout() {
+        let legacy = legacy_curve();
+        let (curve, from_version) = upgrade_curve_data(&legacy_account_data(&legacy)).unwrap();
+        assert_eq!(from_version, 0);
+
+        let mut data = Vec::new();
+        curve.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + BondingCurve::INIT_SPACE);
+
+        let migrated = BondingCurve::try_deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(migrated.version, BondingCurve::CURRENT_VERSION);
+        assert_eq!(migrated.mint, legacy.mint);
+        assert_eq!(migrated.creator, legacy.creator);
+        assert_eq!(migrated.virtual_sol_reserves, legacy.virtual_sol_reserves);
+        assert_eq!(migrated.virtual_token_reserves, legacy.virtual_token_reserves);
+        assert_eq!(migrated.real_sol_reserves, legacy.real_sol_reserves);
+        assert_eq!(migrated.real_token_reserves, legacy.real_token_reserves);
+        assert_eq!(migrated.tokens_sold, legacy.tokens_sold);
+        assert_eq!(migrated.is_graduated, legacy.is_graduated);
+        assert_eq!(migrated.graduation_threshold, migrated.max_sol_raised());
+        assert_eq!(migrated.total_fees_collected, legacy.total_fees_collected);
+        assert_eq!(migrated.creator_fees_pending, legacy.creator_fees_pending);
+        assert_eq!(migrated.total_fees_pending(), legacy.creator_fees_pending);
+        assert!(!migrated.paused);
+        assert_eq!(migrated.bump, legacy.bump);
+    }
+
+    #[test]
+    fn upgrades_version_one_curve() {
+        let mut curve = BondingCurve::from_legacy(legacy_curve());
+        curve.version = 1;
+        let mut data = Vec::new();
+        curve.try_serialize(&mut data).unwrap();
+
+        let (upgraded, from_version) = upgrade_curve_data(&data).unwrap();
+        assert_eq!(from_version, 1);
+        assert_eq!(upgraded.version, BondingCurve::CURRENT_VERSION);
+        assert_eq!(upgraded.real_sol_reserves, curve.real_sol_reserves);
+    }
+
+    #[test]
+    fn converts_threshold_to_real_sol_raised() {
+        let mut curve = BondingCurve::from_legacy(legacy_curve());
+        curve.version = 2;
+        curve.graduation_threshold = curve.virtual_sol_reserves + 50 * LAMPORTS_PER_SOL;
+        let mut data = Vec::new();
+        curve.try_serialize(&mut data).unwrap();
+
+        let (upgraded, from_version) = upgrade_curve_data(&data).unwrap();
+        assert_eq!(from_version, 2);
+        assert_eq!(upgraded.graduation_threshold, 50 * LAMPORTS_PER_SOL);
+    }
+
+    #[test]
+    fn graduates_legacy_curve_past_new_threshold() {
+        // 30 SOL virtual + 10 SOL real becomes a 10 SOL threshold, below the 12 SOL raised
+        let legacy = BondingCurveV0 {
+            graduation_threshold: 40 * LAMPORTS_PER_SOL,
+            ..legacy_curve()
+        };
+        let (mut curve, _) = upgrade_curve_data(&legacy_account_data(&legacy)).unwrap();
+        assert_eq!(curve.graduation_threshold, 10 * LAMPORTS_PER_SOL);
+        assert_eq!(curve.sol_to_complete().unwrap(), 0);
+
+        assert!(curve.graduate_if_threshold_reached());
+        assert!(curve.is_graduated);
+        assert!(!curve.graduate_if_threshold_reached());
+
+        let (mut below, _) = upgrade_curve_data(&legacy_account_data(&legacy_curve())).unwrap();
+        assert!(!below.graduate_if_threshold_reached());
+        assert!(!below.is_graduated);
+    }
+
+    #[test]
+    fn caps_pending_fees_at_vault_backing() {
+        let mut curve = BondingCurve::from_legacy(legacy_curve());
+        curve.platform_fees_pending = 30;
+        curve.prize_pool_fees_pending = 20;
+        curve.reserves_fees_pending = 10;
+        curve.creator_fees_pending = 50;
+
+        // Creator fees go first
+        assert_eq!(curve.cap_pending_fees(80), 30);
+        assert_eq!(curve.creator_fees_pending, 20);
+        assert_eq!(curve.total_fees_pending(), 80);
+
+        assert_eq!(curve.cap_pending_fees(40), 40);
+        assert_eq!(curve.creator_fees_pending, 0);
+        assert_eq!(
+            (curve.platform_fees_pending, curve.prize_pool_fees_pending, curve.reserves_fees_pending),
+            (30, 10, 0)
+        );
+
+        assert_eq!(curve.cap_pending_fees(100), 0);
+        assert_eq!(curve.total_fees_pending(), 40);
+    }
+
+    #[test]
+    fn rejects_already_migrated_curve() {
+        let curve = BondingCurve::from_legacy(legacy_curve());
+        let mut data = Vec::new();
+        curve.try_serialize(&mut data).unwrap();
+
+        assert_eq!(
+            upgrade_curve_data(&data).err(),
+            Some(BondingCurveError::CurveAlreadyMigrated.into())
+        );
+    }
+
+    fn standard_curve() -> BondingCurve {
+        let preset = CurvePreset::defaults().remove(0);
+        let mut curve = BondingCurve::from_legacy(legacy_curve());
+        curve.virtual_sol_reserves = preset.virtual_sol_reserves;
+        curve.virtual_token_reserves = preset.virtual_token_reserves;
+        curve.real_sol_reserves = 0;
+        curve.real_token_reserves = preset.bonding_curve_supply;
+        curve.tokens_sold = 0;
+        curve.graduation_threshold = preset.graduation_threshold;
+        curve
+    }
+
+    #[test]
+    fn fills_buy_below_threshold_in_full() {
+        let curve = standard_curve();
+        let fill = curve.fill_buy(&FeeSchedule::PRE_GRADUATION, LAMPORTS_PER_SOL).unwrap();
+        assert_eq!(fill.sol_in, LAMPORTS_PER_SOL);
+        assert_eq!(fill.sol_to_curve + fill.fees.total(), LAMPORTS_PER_SOL);
+        assert!(!fill.completes_curve);
+    }
+
+    #[test]
+    fn caps_buy_crossing_threshold() {
+        let mut curve = standard_curve();
+        curve.real_sol_reserves = curve.graduation_threshold - LAMPORTS_PER_SOL;
+        let fill = curve.fill_buy(&FeeSchedule::PRE_GRADUATION, 10 * LAMPORTS_PER_SOL).unwrap();
+        assert!(fill.completes_curve);
+        assert!(fill.sol_in < 2 * LAMPORTS_PER_SOL);
+        // Each fee bucket rounds down on its own, so the fill can land a few lamports over
+        assert!(fill.sol_to_curve.abs_diff(LAMPORTS_PER_SOL) <= 4);
+        assert_eq!(fill.sol_to_curve + fill.fees.total(), fill.sol_in);
+        assert!(fill.tokens_out <= curve.real_token_reserves);
+    }
+
+    #[test]
+    fn caps_buy_at_remaining_supply() {
+        let mut curve = standard_curve();
+        curve.graduation_threshold = u64::MAX;
+        let fill = curve.fill_buy(&FeeSchedule::PRE_GRADUATION, 1_000 * LAMPORTS_PER_SOL).unwrap();
+        assert!(fill.completes_curve);
+        assert_eq!(fill.tokens_out, curve.real_token_reserves);
+        assert!(fill.sol_in < 100 * LAMPORTS_PER_SOL);
+    }
+
+    #[test]
+    fn accumulates_time_weighted_price() {
+        let mut curve = standard_curve();
+        curve.last_update_slot = 100;
+        let price = curve.spot_price_q64();
+
+        curve.update_price_accumulator(110);
+        assert_eq!(curve.price_cumulative, price * 10);
+
+        // A second trade in the same slot adds nothing
+        curve.update_price_accumulator(110);
+        assert_eq!(curve.price_cumulative, price * 10);
+    }
+
+    #[test]
+    fn computes_twap_from_observations() {
+        let mut curve = standard_curve();
+        curve.last_update_slot = 100;
+        let mut observations = PriceObservations {
+            mint: curve.mint,
+            head: 0,
+            count: 0,
+            observations: [PriceObservation::default(); PriceObservations::CAPACITY],
+            bump: 0,
+        };
+        observations.record(&curve);
+        let low = curve.spot_price_q64();
+
+        curve.update_price_accumulator(110);
+        curve.real_sol_reserves += 30 * LAMPORTS_PER_SOL;
+        observations.record(&curve);
+        let high = curve.spot_price_q64();
+
+        curve.update_price_accumulator(120);
+        observations.record(&curve);
+        let current = *observations.latest().unwrap();
+
+        assert_eq!(observations.count, 3);
+        assert_eq!(observations.twap(&current, 20), Some((low + high) / 2));
+        assert_eq!(observations.twap(&current, 10), Some(high));
+        assert_eq!(observations.twap(&current, 30), None);
+    }
+
+    #[test]
+    fn observation_buffer_wraps() {
+        let mut curve = standard_curve();
+        let mut observations = PriceObservations {
+            mint: curve.mint,
+            head: 0,
+            count: 0,
+            observations: [PriceObservation::default(); PriceObservations::CAPACITY],
+            bump: 0,
+        };
+        for slot in 1..=(PriceObservations::CAPACITY as u64 + 5) {
+            curve.update_price_accumulator(slot);
+            observations.record(&curve);
+        }
+        assert_eq!(observations.count as usize, PriceObservations::CAPACITY);
+        assert_eq!(observations.latest().unwrap().slot, PriceObservations::CAPACITY as u64 + 5);
+        assert!(observations.at_or_before(5).is_none());
+        assert_eq!(observations.at_or_before(6).unwrap().slot, 6);
+    }
+
+    #[test]
+    fn default_curve_presets_are_valid() {
+        for preset in CurvePreset::defaults() {
+            assert!(preset.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_supply_at_or_above_virtual_token_reserves() {
+        let mut preset = CurvePreset::defaults().remove(0);
+        preset.bonding_curve_supply = preset.virtual_token_reserves;
+        assert_eq!(
+            preset.validate().err(),
+            Some(BondingCurveError::InvalidCurveParameters.into())
+        );
+
+        preset.bonding_curve_supply = 0;
+        assert!(preset.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unreachable_graduation_threshold() {
+        let mut preset = CurvePreset::defaults().remove(0);
+        preset.graduation_threshold = preset.max_sol_raised() + 1;
+        assert_eq!(
+            preset.validate().err(),
+            Some(BondingCurveError::InvalidGraduationThreshold.into())
+        );
+    }
+
+    #[test]
+    fn rejects_zero_virtual_sol_reserves() {
+        let mut preset = CurvePreset::defaults().remove(0);
+        preset.virtual_sol_reserves = 0;
+        assert_eq!(
+            preset.validate().err(),
+            Some(BondingCurveError::InvalidCurveParameters.into())
+        );
+    }
+
+    #[test]
+    fn rejects_foreign_account_data() {
+        let mut data = legacy_account_data(&legacy_curve());
+        data[..8].copy_from_slice(&Config::DISCRIMINATOR);
+
+        assert_eq!(
+            upgrade_curve_data(&data).err(),
+            Some(BondingCurveError::InvalidCurveAccount.into())
+        );
+    }
+
+    #[test]
+    fn bounds_config_delay() {
+        let delay = |delay_seconds| ConfigChange::ConfigDelay { delay_seconds }.validate();
+
+        assert!(delay(Config::MIN_CONFIG_DELAY_SECONDS).is_ok());
+        assert!(delay(Config::MAX_CONFIG_DELAY_SECONDS).is_ok());
+        assert_eq!(
+            delay(Config::MIN_CONFIG_DELAY_SECONDS - 1).err(),
+            Some(BondingCurveError::ConfigDelayTooShort.into())
+        );
+        assert_eq!(
+            delay(i64::MAX).err(),
+            Some(BondingCurveError::ConfigDelayTooLong.into())
+        );
+    }
+
+    #[test]
+    fn rechecks_proposer_role_at_execution() {
+        let fee_manager = Pubkey::new_unique();
+        let mut config = Config {
+            admin: Pubkey::new_unique(),
+            fee_manager,
+            ..Config::try_deserialize_unchecked(&mut &[0u8; 8 + Config::INIT_SPACE][..]).unwrap()
+        };
+        let pending_change = PendingConfigChange {
+            id: 0,
+            change: ConfigChange::ReferralShare { referral_share_bps: 1000 },
+            proposer: fee_manager,
+            queued_at: 0,
+            eta: 100,
+            bump: 0,
+        };
+
+        assert_eq!(
+            pending_change.check_executable(&config, 99).err(),
+            Some(BondingCurveError::ConfigChangeNotReady.into())
+        );
+        assert!(pending_change.check_executable(&config, 100).is_ok());
+
+        config.fee_manager = Pubkey::new_unique();
+        assert_eq!(
+            pending_change.check_executable(&config, 100).err(),
+            Some(BondingCurveError::ProposerRoleRevoked.into())
+        );
+    }
+}
@@ -1,13 +1,61 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::Discriminator;
+use anchor_spl::token::{
+    self, spl_token::native_mint, CloseAccount, Mint, SyncNative, Token, TokenAccount, Transfer,
+};
+use amm::program::Amm;
+use amm::Pool;
+use crate::program::FeeWrapper as FeeWrapperProgram;
+use staking::StakePosition;
+use trade_stats::{FeeTier, TradeStats};
 
-declare_id!("11111111111111111111111111111111");
+declare_id!("9VTFhx3g1XXfv4A14SVuTbokWNeFurRVJF1Lfa69Wq24");
 
 #[program]
 pub mod fee_wrapper {
     use super::*;
 
+    /// Initialize the wrapper config. Only the program's upgrade authority can initialize;
+    /// it becomes admin.
+    /// The graduation authority is derived as `bonding_curve_program`'s
+    /// `[b"graduation_authority"]` PDA, which signs the `initialize_wrapper` CPI made by `graduate`.
+    /// `amm_program` is the AMM wrapped trades are routed through.
+    /// `platform_wallet` owns the wSOL account platform fees are paid to.
+    pub fn initialize_wrapper_config(
+        ctx: Context<InitializeWrapperConfig>,
+        bonding_curve_program: Pubkey,
+        amm_program: Pubkey,
+        platform_wallet: Pubkey,
+    ) -> Result<()> {
+        let (graduation_authority, _) =
+            Pubkey::find_program_address(&[b"graduation_authority"], &bonding_curve_program);
+
+        let config = &mut ctx.accounts.wrapper_config;
+        config.admin = ctx.accounts.admin.key();
+        config.graduation_authority = graduation_authority;
+        config.amm_program = amm_program;
+        config.platform_wallet = platform_wallet;
+        config.platform_token_mint = Pubkey::default();
+        config.fee_tiers = Vec::new();
+        config.pending_platform_token_mint = Pubkey::default();
+        config.pending_fee_tiers = Vec::new();
+        config.fee_tiers_eta = 0;
+        config.bump = ctx.bumps.wrapper_config;
+        config.reserved = [0; WrapperConfig::RESERVED_BYTES];
+
+        emit!(WrapperConfigInitialized {
+            admin: config.admin,
+            bonding_curve_program,
+            graduation_authority,
+            amm_program,
+            platform_wallet,
+        });
+
+        Ok(())
+    }
+
     /// Initialize wrapper for a graduated token
+    /// Only callable by the bonding curve's graduation CPI or the platform admin
     pub fn initialize_wrapper(
         ctx: Context<InitializeWrapper>,
         token_mint: Pubkey,
@@ -15,6 +63,8 @@ pub mod fee_wrapper {
         platform_fee_bps: u16,
         creator_fee_bps: u16,
     ) -> Result<()> {
+        validate_fees(platform_fee_bps, creator_fee_bps)?;
+
         let wrapper = &mut ctx.accounts.wrapper;
         wrapper.token_mint = token_mint;
         wrapper.creator_wallet = creator_wallet;
@@ -26,6 +76,10 @@ pub mod fee_wrapper {
         wrapper.platform_fees_earned = 0;
         wrapper.is_active = true;
         wrapper.created_at = Clock::get()?.unix_timestamp;
+        wrapper.pending_platform_fee_bps = 0;
+        wrapper.pending_creator_fee_bps = 0;
+        wrapper.fee_update_eta = 0;
+        wrapper.reserved = [0; FeeWrapper::RESERVED_BYTES];
         
         emit!(WrapperInitialized {
             wrapper: wrapper.key(),
@@ -33,58 +87,104 @@ pub mod fee_wrapper {
             creator_wallet,
             platform_fee_bps,
             creator_fee_bps,
+            authority: ctx.accounts.authority.key(),
         });
         
         Ok(())
     }
 
     /// Execute wrapped trade with fee collection
+    /// Buys take fees in SOL from the input before swapping it for tokens.
+    /// Sells swap the tokens first and take fees in SOL from the output.
+    /// `min_amount_out` bounds what the user receives after fees: tokens on buys, SOL on sells.
+    /// The user trades native SOL: it is wrapped into a temporary wSOL account that is
+    /// closed back to the user at the end, which also unwraps sale proceeds.
     pub fn execute_wrapper_trade(
         ctx: Context<ExecuteWrapperTrade>,
         trade_amount: u64,
+        min_amount_out: u64,
         trade_type: TradeType,
     ) -> Result<()> {
-        let wrapper = &mut ctx.accounts.wrapper;
-        
-        require!(wrapper.is_active, ErrorCode::WrapperInactive);
-        
-        // Calculate fees
-        let total_fee_bps = wrapper.platform_fee_bps + wrapper.creator_fee_bps;
-        let total_fees = (trade_amount as u128 * total_fee_bps as u128 / 10000) as u64;
-        let platform_fee = (total_fees as u128 * wrapper.platform_fee_bps as u128 / total_fee_bps as u128) as u64;
-        let creator_fee = total_fees - platform_fee;
-        let trade_amount_after_fees = trade_amount - total_fees;
-
-        // Transfer platform fee
-        if platform_fee > 0 {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.user_sol_account.to_account_info(),
-                to: ctx.accounts.platform_fee_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, platform_fee)?;
-        }
+        require!(ctx.accounts.wrapper.is_active, ErrorCode::WrapperInactive);
+        check_pool_vaults(
+            &ctx.accounts.amm_pool,
+            &ctx.accounts.amm_sol_vault.key(),
+            &ctx.accounts.amm_token_vault.key(),
+        )?;
 
-        // Transfer creator fee
-        if creator_fee > 0 {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.user_sol_account.to_account_info(),
-                to: ctx.accounts.creator_fee_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, creator_fee)?;
+        let now = Clock::get()?.unix_timestamp;
+        let volume_30d = ctx
+            .accounts
+            .user_stats
+            .as_ref()
+            .map_or(0, |user_stats| user_stats.stats.volume_30d(now));
+        let staked = ctx.accounts.stake_position.as_ref().map_or(0, |position| position.amount);
+        let fee_tier = ctx.accounts.wrapper_config.fee_tier(volume_30d, staked);
+        let platform_discount_bps = fee_tier.map_or(0, |tier| tier.platform_discount_bps);
+
+        if trade_type == TradeType::Buy {
+            wrap_sol(ctx.accounts, trade_amount)?;
         }
 
+        let WrapperTradeFill {
+            sol_volume,
+            total_fees,
+            platform_fee,
+            creator_fee,
+            trade_amount_after_fees,
+            amount_out,
+        } = ctx.accounts.wrapper.settle_trade(
+            &trade_type,
+            trade_amount,
+            min_amount_out,
+            platform_discount_bps,
+            |amount_in| swap(&ctx, amount_in, &trade_type),
+        )?;
+        collect_fees(ctx.accounts, platform_fee, creator_fee)?;
+
+        // Return the temporary account's rent and any wSOL left in it as native SOL
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.temp_wsol_account.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::close_account(CpiContext::new(cpi_program, cpi_accounts))?;
+
         // Update wrapper stats
-        wrapper.total_volume = wrapper.total_volume.checked_add(trade_amount).unwrap();
-        wrapper.total_fees_collected = wrapper.total_fees_collected.checked_add(total_fees).unwrap();
-        wrapper.platform_fees_earned = wrapper.platform_fees_earned.checked_add(platform_fee).unwrap();
-        wrapper.creator_fees_earned = wrapper.creator_fees_earned.checked_add(creator_fee).unwrap();
+        let wrapper = &mut ctx.accounts.wrapper;
+        wrapper.total_volume = wrapper
+            .total_volume
+            .checked_add(sol_volume)
+            .ok_or(ErrorCode::MathOverflow)?;
+        wrapper.total_fees_collected = wrapper
+            .total_fees_collected
+            .checked_add(total_fees)
+            .ok_or(ErrorCode::MathOverflow)?;
+        wrapper.platform_fees_earned = wrapper
+            .platform_fees_earned
+            .checked_add(platform_fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        wrapper.creator_fees_earned = wrapper
+            .creator_fees_earned
+            .checked_add(creator_fee)
+            .ok_or(ErrorCode::MathOverflow)?;
 
+        let (sol_in, sol_out) = match trade_type {
+            TradeType::Buy => (trade_amount, 0),
+            TradeType::Sell => (0, amount_out),
+        };
+        for stats in [ctx.accounts.user_mint_stats.as_deref_mut(), ctx.accounts.user_stats.as_deref_mut()]
+            .into_iter()
+            .flatten()
+        {
+            stats
+                .stats
+                .record_trade(sol_volume, sol_in, sol_out, now)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let wrapper = &ctx.accounts.wrapper;
         emit!(WrapperTradeExecuted {
             wrapper: wrapper.key(),
             user: ctx.accounts.user.key(),
@@ -94,26 +194,311 @@ pub mod fee_wrapper {
             platform_fee,
             creator_fee,
             trade_type,
+            amount_out,
+            fee_tier,
         });
 
         Ok(())
     }
 
     /// Update wrapper status
+    /// Requires both the creator and the platform admin, so neither can switch off the other's fees
     pub fn update_wrapper_status(
         ctx: Context<UpdateWrapperStatus>,
         is_active: bool,
     ) -> Result<()> {
         let wrapper = &mut ctx.accounts.wrapper;
+        let was_active = wrapper.is_active;
         wrapper.is_active = is_active;
         
         emit!(WrapperStatusUpdated {
             wrapper: wrapper.key(),
+            was_active,
             is_active,
         });
         
         Ok(())
     }
+
+    /// Queue new fee rates for a wrapper. Platform admin only.
+    /// They take effect through `execute_fee_update` once `FEE_UPDATE_DELAY_SECONDS` have passed.
+    pub fn update_fees(
+        ctx: Context<UpdateFees>,
+        platform_fee_bps: u16,
+        creator_fee_bps: u16,
+    ) -> Result<()> {
+        validate_fees(platform_fee_bps, creator_fee_bps)?;
+
+        let wrapper = &mut ctx.accounts.wrapper;
+        wrapper.pending_platform_fee_bps = platform_fee_bps;
+        wrapper.pending_creator_fee_bps = creator_fee_bps;
+        wrapper.fee_update_eta = Clock::get()?.unix_timestamp + FeeWrapper::FEE_UPDATE_DELAY_SECONDS;
+
+        emit!(FeeUpdateQueued {
+            wrapper: wrapper.key(),
+            old_platform_fee_bps: wrapper.platform_fee_bps,
+            old_creator_fee_bps: wrapper.creator_fee_bps,
+            new_platform_fee_bps: platform_fee_bps,
+            new_creator_fee_bps: creator_fee_bps,
+            eta: wrapper.fee_update_eta,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a queued fee update once its timelock has elapsed. Permissionless.
+    pub fn execute_fee_update(ctx: Context<ExecuteFeeUpdate>) -> Result<()> {
+        let wrapper = &mut ctx.accounts.wrapper;
+        require!(wrapper.fee_update_eta != 0, ErrorCode::NoPendingFeeUpdate);
+        require!(
+            Clock::get()?.unix_timestamp >= wrapper.fee_update_eta,
+            ErrorCode::FeeUpdateNotReady
+        );
+
+        let old_platform_fee_bps = wrapper.platform_fee_bps;
+        let old_creator_fee_bps = wrapper.creator_fee_bps;
+        wrapper.platform_fee_bps = wrapper.pending_platform_fee_bps;
+        wrapper.creator_fee_bps = wrapper.pending_creator_fee_bps;
+        wrapper.fee_update_eta = 0;
+
+        emit!(FeesUpdated {
+            wrapper: wrapper.key(),
+            old_platform_fee_bps,
+            old_creator_fee_bps,
+            new_platform_fee_bps: wrapper.platform_fee_bps,
+            new_creator_fee_bps: wrapper.creator_fee_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Drop a queued fee update. The platform admin may cancel any update; the creator only
+    /// one that changes the creator fee.
+    pub fn cancel_fee_update(ctx: Context<CancelFeeUpdate>) -> Result<()> {
+        let wrapper = &mut ctx.accounts.wrapper;
+        require!(wrapper.fee_update_eta != 0, ErrorCode::NoPendingFeeUpdate);
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.wrapper_config.admin
+                || wrapper.changes_creator_fee(),
+            ErrorCode::Unauthorized
+        );
+        wrapper.fee_update_eta = 0;
+
+        emit!(FeeUpdateCancelled {
+            wrapper: wrapper.key(),
+            cancelled_by: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Queue a new fee tier table. Platform admin only.
+    /// It takes effect through `execute_fee_tier_update` once `FEE_UPDATE_DELAY_SECONDS` have passed.
+    pub fn update_fee_tiers(
+        ctx: Context<UpdateFeeTiers>,
+        platform_token_mint: Pubkey,
+        fee_tiers: Vec<FeeTier>,
+    ) -> Result<()> {
+        require!(FeeTier::is_valid_table(&fee_tiers), ErrorCode::InvalidFeeTier);
+
+        let config = &mut ctx.accounts.wrapper_config;
+        config.pending_platform_token_mint = platform_token_mint;
+        config.pending_fee_tiers = fee_tiers;
+        config.fee_tiers_eta = Clock::get()?.unix_timestamp + FeeWrapper::FEE_UPDATE_DELAY_SECONDS;
+
+        emit!(FeeTiersQueued {
+            platform_token_mint,
+            fee_tiers: config.pending_fee_tiers.clone(),
+            eta: config.fee_tiers_eta,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a queued fee tier table once its timelock has elapsed. Permissionless.
+    pub fn execute_fee_tier_update(ctx: Context<ExecuteFeeTierUpdate>) -> Result<()> {
+        let config = &mut ctx.accounts.wrapper_config;
+        require!(config.fee_tiers_eta != 0, ErrorCode::NoPendingFeeUpdate);
+        require!(
+            Clock::get()?.unix_timestamp >= config.fee_tiers_eta,
+            ErrorCode::FeeUpdateNotReady
+        );
+
+        config.platform_token_mint = config.pending_platform_token_mint;
+        config.fee_tiers = std::mem::take(&mut config.pending_fee_tiers);
+        config.pending_platform_token_mint = Pubkey::default();
+        config.fee_tiers_eta = 0;
+
+        emit!(FeeTiersUpdated {
+            platform_token_mint: config.platform_token_mint,
+            fee_tiers: config.fee_tiers.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Drop a queued fee tier table. Platform admin only.
+    pub fn cancel_fee_tier_update(ctx: Context<UpdateFeeTiers>) -> Result<()> {
+        let config = &mut ctx.accounts.wrapper_config;
+        require!(config.fee_tiers_eta != 0, ErrorCode::NoPendingFeeUpdate);
+        config.pending_platform_token_mint = Pubkey::default();
+        config.pending_fee_tiers = Vec::new();
+        config.fee_tiers_eta = 0;
+
+        emit!(FeeTierUpdateCancelled {
+            cancelled_by: ctx.accounts.admin.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Create a wallet's wrapped-trade stats, for one token when `mint` is passed, otherwise across all tokens.
+    /// Pass them to `execute_wrapper_trade` to keep them updated.
+    pub fn initialize_user_stats(ctx: Context<InitializeUserStats>, mint: Option<Pubkey>) -> Result<()> {
+        let stats = &mut ctx.accounts.user_stats;
+        stats.user = ctx.accounts.user.key();
+        stats.mint = mint.unwrap_or_default();
+        stats.stats = TradeStats::default();
+        stats.bump = ctx.bumps.user_stats;
+        stats.reserved = [0; UserStats::RESERVED_BYTES];
+        Ok(())
+    }
+
+    /// Upgrade a wrapper created with the original layout, reallocating it in place.
+    /// No fee update is queued afterwards. The payer covers the extra rent. Permissionless.
+    pub fn migrate_wrapper(ctx: Context<MigrateWrapper>) -> Result<()> {
+        let wrapper_info = ctx.accounts.wrapper.to_account_info();
+        require!(wrapper_info.owner == &crate::ID, ErrorCode::InvalidWrapperAccount);
+
+        let old_len = wrapper_info.data_len();
+        let wrapper = upgrade_wrapper_data(&wrapper_info.try_borrow_data()?)?;
+        let new_len = 8 + FeeWrapper::INIT_SPACE;
+
+        let rent_top_up = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(wrapper_info.lamports());
+        if rent_top_up > 0 {
+            let cpi_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: wrapper_info.clone(),
+                },
+            );
+            anchor_lang::system_program::transfer(cpi_context, rent_top_up)?;
+        }
+
+        wrapper_info.realloc(new_len, true)?;
+        wrapper.try_serialize(&mut &mut wrapper_info.try_borrow_mut_data()?[..])?;
+
+        emit!(WrapperMigrated {
+            wrapper: wrapper_info.key(),
+            token_mint: wrapper.token_mint,
+            previous_size: old_len as u32,
+        });
+
+        Ok(())
+    }
+}
+
+/// Decode a wrapper account written with the original layout and carry it over to the current one
+fn upgrade_wrapper_data(data: &[u8]) -> Result<FeeWrapper> {
+    require!(
+        data.len() >= 8 && data[..8] == FeeWrapper::DISCRIMINATOR,
+        ErrorCode::InvalidWrapperAccount
+    );
+    require!(
+        data.len() != 8 + FeeWrapper::INIT_SPACE,
+        ErrorCode::WrapperAlreadyMigrated
+    );
+    require!(
+        data.len() == 8 + FeeWrapperV0::LEN,
+        ErrorCode::InvalidWrapperAccount
+    );
+    Ok(FeeWrapper::from_legacy(FeeWrapperV0::deserialize(&mut &data[8..])?))
+}
+
+/// Wrapper fees may total at most `FeeWrapper::MAX_TOTAL_FEE_BPS`
+fn validate_fees(platform_fee_bps: u16, creator_fee_bps: u16) -> Result<()> {
+    require!(
+        platform_fee_bps as u32 + creator_fee_bps as u32 <= FeeWrapper::MAX_TOTAL_FEE_BPS as u32,
+        ErrorCode::InvalidFeePercentage
+    );
+    Ok(())
+}
+
+/// The vaults passed for a swap must be the pool's own
+fn check_pool_vaults(pool: &Pool, sol_vault: &Pubkey, token_vault: &Pubkey) -> Result<()> {
+    require!(
+        *sol_vault == pool.quote_vault && *token_vault == pool.base_vault,
+        ErrorCode::InvalidPoolVault
+    );
+    Ok(())
+}
+
+/// Move native SOL from the user into the temporary wSOL account
+fn wrap_sol(accounts: &ExecuteWrapperTrade, amount: u64) -> Result<()> {
+    let cpi_accounts = anchor_lang::system_program::Transfer {
+        from: accounts.user.to_account_info(),
+        to: accounts.temp_wsol_account.to_account_info(),
+    };
+    let cpi_program = accounts.system_program.to_account_info();
+    anchor_lang::system_program::transfer(CpiContext::new(cpi_program, cpi_accounts), amount)?;
+
+    let cpi_accounts = SyncNative {
+        account: accounts.temp_wsol_account.to_account_info(),
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    token::sync_native(CpiContext::new(cpi_program, cpi_accounts))
+}
+
+/// Move the platform and creator fees out of the temporary wSOL account
+fn collect_fees(accounts: &ExecuteWrapperTrade, platform_fee: u64, creator_fee: u64) -> Result<()> {
+    for (to, fee) in [
+        (&accounts.platform_fee_account, platform_fee),
+        (&accounts.creator_fee_account, creator_fee),
+    ] {
+        if fee > 0 {
+            let cpi_accounts = Transfer {
+                from: accounts.temp_wsol_account.to_account_info(),
+                to: to.to_account_info(),
+                authority: accounts.user.to_account_info(),
+            };
+            let cpi_program = accounts.token_program.to_account_info();
+            token::transfer(CpiContext::new(cpi_program, cpi_accounts), fee)?;
+        }
+    }
+    Ok(())
+}
+
+/// Swap through the configured AMM and return how much the user received
+fn swap(ctx: &Context<ExecuteWrapperTrade>, amount_in: u64, trade_type: &TradeType) -> Result<u64> {
+    let accounts = &ctx.accounts;
+    let (user_source, user_destination) =
+        trade_type.route(&accounts.temp_wsol_account, &accounts.user_token_account);
+    let (source_vault, destination_vault) =
+        trade_type.route(&accounts.amm_sol_vault, &accounts.amm_token_vault);
+    let balance_before = user_destination.amount;
+
+    let cpi_accounts = amm::cpi::accounts::Swap {
+        pool: accounts.amm_pool.to_account_info(),
+        user_source: user_source.to_account_info(),
+        user_destination: user_destination.to_account_info(),
+        source_vault: source_vault.to_account_info(),
+        destination_vault: destination_vault.to_account_info(),
+        user: accounts.user.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+    };
+    let cpi_program = accounts.amm_program.to_account_info();
+    // Slippage is enforced by the wrapper after fees, not by the AMM
+    amm::cpi::swap(CpiContext::new(cpi_program, cpi_accounts), amount_in, 0)?;
+
+    let mut destination = user_destination.clone();
+    destination.reload()?;
+    destination
+        .amount
+        .checked_sub(balance_before)
+        .ok_or_else(|| error!(ErrorCode::SlippageExceeded))
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
@@ -122,9 +507,61 @@ pub enum TradeType {
     Sell,
 }
 
+impl TradeType {
+    /// Order the SOL and token sides of a swap as (source, destination)
+    pub fn route<T>(&self, sol_side: T, token_side: T) -> (T, T) {
+        match self {
+            TradeType::Buy => (sol_side, token_side),
+            TradeType::Sell => (token_side, sol_side),
+        }
+    }
+}
+
+/// Amounts of a wrapper trade, as settled by `FeeWrapper::settle_trade`
+pub struct WrapperTradeFill {
+    pub sol_volume: u64,                  // SOL side of the trade, fees included
+    pub total_fees: u64,
+    pub platform_fee: u64,
+    pub creator_fee: u64,
+    pub trade_amount_after_fees: u64,     // Buy: SOL swapped. Sell: SOL paid out
+    pub amount_out: u64,                  // Buy: tokens received. Sell: SOL paid out
+}
+
+#[derive(Accounts)]
+pub struct InitializeWrapperConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + WrapperConfig::INIT_SPACE,
+        seeds = [b"wrapper_config"],
+        bump
+    )]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+
+    #[account(
+        constraint = fee_wrapper_program.programdata_address()? == Some(program_data.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub fee_wrapper_program: Program<'info, FeeWrapperProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(token_mint: Pubkey)]
 pub struct InitializeWrapper<'info> {
+    #[account(seeds = [b"wrapper_config"], bump = wrapper_config.bump)]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
     #[account(
         init,
         payer = payer,
@@ -134,6 +571,13 @@ pub struct InitializeWrapper<'info> {
     )]
     pub wrapper: Account<'info, FeeWrapper>,
     
+    /// Bonding curve graduation authority (via CPI) or the platform admin
+    #[account(
+        constraint = authority.key() == wrapper_config.graduation_authority
+            || authority.key() == wrapper_config.admin @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+    
     #[account(mut)]
     pub payer: Signer<'info>,
     
@@ -142,6 +586,9 @@ pub struct InitializeWrapper<'info> {
 
 #[derive(Accounts)]
 pub struct ExecuteWrapperTrade<'info> {
+    #[account(seeds = [b"wrapper_config"], bump = wrapper_config.bump)]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
     #[account(
         mut,
         seeds = [b"wrapper", wrapper.token_mint.as_ref()],
@@ -152,20 +599,150 @@ pub struct ExecuteWrapperTrade<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     
-    #[account(mut)]
-    pub user_sol_account: Account<'info, TokenAccount>,
+    /// Temporary wSOL account, created and closed within the trade
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"temp_wsol", user.key().as_ref()],
+        bump,
+        token::mint = wsol_mint,
+        token::authority = user
+    )]
+    pub temp_wsol_account: Account<'info, TokenAccount>,
     
-    #[account(mut)]
+    #[account(address = native_mint::ID)]
+    pub wsol_mint: Account<'info, Mint>,
+    
+    #[account(mut, token::mint = wrapper.token_mint)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = native_mint::ID,
+        token::authority = wrapper_config.platform_wallet
+    )]
     pub platform_fee_account: Account<'info, TokenAccount>,
     
-    #[account(mut)]
+    #[account(
+        mut,
+        token::mint = native_mint::ID,
+        token::authority = wrapper.creator_wallet
+    )]
     pub creator_fee_account: Account<'info, TokenAccount>,
     
+    /// The wrapped token's wSOL pool
+    #[account(
+        seeds = [b"pool", wrapper.token_mint.as_ref(), native_mint::ID.as_ref()],
+        bump = amm_pool.bump,
+        seeds::program = wrapper_config.amm_program
+    )]
+    pub amm_pool: Account<'info, Pool>,
+    
+    /// AMM vault holding wSOL, checked against the pool by `check_pool_vaults`
+    #[account(mut)]
+    pub amm_sol_vault: Account<'info, TokenAccount>,
+    
+    /// AMM vault holding the wrapped token, checked against the pool by `check_pool_vaults`
+    #[account(mut)]
+    pub amm_token_vault: Account<'info, TokenAccount>,
+    
+    #[account(address = wrapper_config.amm_program @ ErrorCode::InvalidAmmProgram)]
+    pub amm_program: Program<'info, Amm>,
+    
+    /// Optional: the user's stats for this token
+    #[account(
+        mut,
+        seeds = [b"user_stats", user.key().as_ref(), wrapper.token_mint.as_ref()],
+        bump = user_mint_stats.bump
+    )]
+    pub user_mint_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the user's stats across all tokens
+    #[account(
+        mut,
+        seeds = [b"user_stats", user.key().as_ref()],
+        bump = user_stats.bump
+    )]
+    pub user_stats: Option<Box<Account<'info, UserStats>>>,
+    
+    /// Optional: the user's platform token stake, for holder fee tiers
+    #[account(
+        seeds = [b"stake", user.key().as_ref()],
+        bump = stake_position.bump,
+        seeds::program = staking::ID,
+        constraint = stake_position.stake_mint == wrapper_config.platform_token_mint
+            @ ErrorCode::InvalidStakeMint
+    )]
+    pub stake_position: Option<Box<Account<'info, StakePosition>>>,
+    
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeTiers<'info> {
+    #[account(
+        mut,
+        seeds = [b"wrapper_config"],
+        bump = wrapper_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateWrapper<'info> {
+    /// CHECK: Deserialized by hand since it may still use the original layout
+    #[account(
+        mut,
+        seeds = [b"wrapper", token_mint.key().as_ref()],
+        bump
+    )]
+    pub wrapper: UncheckedAccount<'info>,
+
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteFeeTierUpdate<'info> {
+    #[account(mut, seeds = [b"wrapper_config"], bump = wrapper_config.bump)]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Option<Pubkey>)]
+pub struct InitializeUserStats<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [b"user_stats".as_ref(), user.key().as_ref(), mint.as_ref().map_or(&[][..], |mint| mint.as_ref())],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct UpdateWrapperStatus<'info> {
+    #[account(
+        seeds = [b"wrapper_config"],
+        bump = wrapper_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
     #[account(
         mut,
         seeds = [b"wrapper", wrapper.token_mint.as_ref()],
@@ -175,6 +752,84 @@ pub struct UpdateWrapperStatus<'info> {
     pub wrapper: Account<'info, FeeWrapper>,
     
     pub creator_wallet: Signer<'info>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFees<'info> {
+    #[account(
+        seeds = [b"wrapper_config"],
+        bump = wrapper_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"wrapper", wrapper.token_mint.as_ref()],
+        bump
+    )]
+    pub wrapper: Account<'info, FeeWrapper>,
+    
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteFeeUpdate<'info> {
+    #[account(
+        mut,
+        seeds = [b"wrapper", wrapper.token_mint.as_ref()],
+        bump
+    )]
+    pub wrapper: Account<'info, FeeWrapper>,
+}
+
+#[derive(Accounts)]
+pub struct CancelFeeUpdate<'info> {
+    #[account(seeds = [b"wrapper_config"], bump = wrapper_config.bump)]
+    pub wrapper_config: Account<'info, WrapperConfig>,
+    
+    #[account(
+        mut,
+        seeds = [b"wrapper", wrapper.token_mint.as_ref()],
+        bump
+    )]
+    pub wrapper: Account<'info, FeeWrapper>,
+    
+    /// Platform admin, or the wrapper's creator for updates that change the creator fee
+    #[account(
+        constraint = authority.key() == wrapper_config.admin
+            || authority.key() == wrapper.creator_wallet @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WrapperConfig {
+    pub admin: Pubkey,
+    pub graduation_authority: Pubkey,    // Bonding curve PDA allowed to create wrappers
+    pub amm_program: Pubkey,             // AMM wrapped trades are routed through
+    pub platform_wallet: Pubkey,         // Owner of the platform fee wSOL account
+    pub platform_token_mint: Pubkey,     // Token whose stakers qualify for holder fee tiers
+    #[max_len(8)]
+    pub fee_tiers: Vec<FeeTier>,         // Platform fee discounts by 30-day volume or stake
+    pub pending_platform_token_mint: Pubkey, // Queued by `update_fee_tiers`
+    #[max_len(8)]
+    pub pending_fee_tiers: Vec<FeeTier>,
+    pub fee_tiers_eta: i64,              // 0 when no tier update is queued
+    pub bump: u8,
+    pub reserved: [u8; WrapperConfig::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl WrapperConfig {
+    pub const RESERVED_BYTES: usize = 64;
+
+    /// The qualifying fee tier with the largest discount
+    pub fn fee_tier(&self, volume_30d: u64, staked: u64) -> Option<FeeTier> {
+        FeeTier::best(&self.fee_tiers, volume_30d, staked)
+    }
 }
 
 #[account]
@@ -190,6 +845,140 @@ pub struct FeeWrapper {
     pub platform_fees_earned: u64,
     pub is_active: bool,
     pub created_at: i64,
+    pub pending_platform_fee_bps: u16,  // Queued by `update_fees`
+    pub pending_creator_fee_bps: u16,
+    pub fee_update_eta: i64,            // 0 when no fee update is queued
+    pub reserved: [u8; FeeWrapper::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl FeeWrapper {
+    pub const RESERVED_BYTES: usize = 64;
+    pub const MAX_TOTAL_FEE_BPS: u16 = 1000;
+    pub const FEE_UPDATE_DELAY_SECONDS: i64 = 48 * 60 * 60;
+
+    /// Carry an original-layout wrapper over to the current layout, with no fee update queued
+    pub fn from_legacy(legacy: FeeWrapperV0) -> Self {
+        Self {
+            token_mint: legacy.token_mint,
+            creator_wallet: legacy.creator_wallet,
+            platform_fee_bps: legacy.platform_fee_bps,
+            creator_fee_bps: legacy.creator_fee_bps,
+            total_volume: legacy.total_volume,
+            total_fees_collected: legacy.total_fees_collected,
+            creator_fees_earned: legacy.creator_fees_earned,
+            platform_fees_earned: legacy.platform_fees_earned,
+            is_active: legacy.is_active,
+            created_at: legacy.created_at,
+            pending_platform_fee_bps: 0,
+            pending_creator_fee_bps: 0,
+            fee_update_eta: 0,
+            reserved: [0; Self::RESERVED_BYTES],
+        }
+    }
+
+    /// Whether the queued fee update changes the creator fee
+    pub fn changes_creator_fee(&self) -> bool {
+        self.fee_update_eta != 0 && self.pending_creator_fee_bps != self.creator_fee_bps
+    }
+
+    /// Total, platform and creator fees on a SOL amount, with `platform_discount_bps` of the
+    /// platform fee waived
+    pub fn split_fees(&self, amount: u64, platform_discount_bps: u16) -> (u64, u64, u64) {
+        let platform_fee_bps =
+            self.platform_fee_bps as u128 * (10000 - platform_discount_bps as u128) / 10000;
+        let platform_fee = (amount as u128 * platform_fee_bps / 10000) as u64;
+        let creator_fee = (amount as u128 * self.creator_fee_bps as u128 / 10000) as u64;
+        (platform_fee + creator_fee, platform_fee, creator_fee)
+    }
+
+    /// Take fees around `swap`, which swaps an input amount and returns the output.
+    /// Buys pay fees out of the SOL going in, sells out of the SOL coming back, and slippage
+    /// is checked after fees either way.
+    pub fn settle_trade(
+        &self,
+        trade_type: &TradeType,
+        trade_amount: u64,
+        min_amount_out: u64,
+        platform_discount_bps: u16,
+        swap: impl FnOnce(u64) -> Result<u64>,
+    ) -> Result<WrapperTradeFill> {
+        match trade_type {
+            TradeType::Buy => {
+                let (total_fees, platform_fee, creator_fee) =
+                    self.split_fees(trade_amount, platform_discount_bps);
+                let trade_amount_after_fees = trade_amount - total_fees;
+                let tokens_out = swap(trade_amount_after_fees)?;
+                require!(tokens_out >= min_amount_out, ErrorCode::SlippageExceeded);
+                Ok(WrapperTradeFill {
+                    sol_volume: trade_amount,
+                    total_fees,
+                    platform_fee,
+                    creator_fee,
+                    trade_amount_after_fees,
+                    amount_out: tokens_out,
+                })
+            }
+            TradeType::Sell => {
+                let sol_out = swap(trade_amount)?;
+                let (total_fees, platform_fee, creator_fee) =
+                    self.split_fees(sol_out, platform_discount_bps);
+                let sol_after_fees = sol_out - total_fees;
+                require!(sol_after_fees >= min_amount_out, ErrorCode::SlippageExceeded);
+                Ok(WrapperTradeFill {
+                    sol_volume: sol_out,
+                    total_fees,
+                    platform_fee,
+                    creator_fee,
+                    trade_amount_after_fees: sol_after_fees,
+                    amount_out: sol_after_fees,
+                })
+            }
+        }
+    }
+}
+
+/// Original `FeeWrapper` layout, kept for `migrate_wrapper`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FeeWrapperV0 {
+    pub token_mint: Pubkey,
+    pub creator_wallet: Pubkey,
+    pub platform_fee_bps: u16,
+    pub creator_fee_bps: u16,
+    pub total_volume: u64,
+    pub total_fees_collected: u64,
+    pub creator_fees_earned: u64,
+    pub platform_fees_earned: u64,
+    pub is_active: bool,
+    pub created_at: i64,
+}
+
+impl FeeWrapperV0 {
+    pub const LEN: usize = 32 + 32 + 2 + 2 + 8 + 8 + 8 + 8 + 1 + 8;
+}
+
+/// A wallet's wrapped trading, per token at `[b"user_stats", user, mint]` and across all
+/// tokens at `[b"user_stats", user]` (with `mint` left as the default pubkey)
+#[account]
+#[derive(InitSpace)]
+pub struct UserStats {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub stats: TradeStats,              // Volume counts SOL traded, before fees
+    pub bump: u8,
+    pub reserved: [u8; UserStats::RESERVED_BYTES], // Space for future fields without a realloc
+}
+
+impl UserStats {
+    pub const RESERVED_BYTES: usize = 64;
+}
+
+#[event]
+pub struct WrapperConfigInitialized {
+    pub admin: Pubkey,
+    pub bonding_curve_program: Pubkey,
+    pub graduation_authority: Pubkey,
+    pub amm_program: Pubkey,
+    pub platform_wallet: Pubkey,
 }
 
 #[event]
@@ -199,6 +988,7 @@ pub struct WrapperInitialized {
     pub creator_wallet: Pubkey,
     pub platform_fee_bps: u16,
     pub creator_fee_bps: u16,
+    pub authority: Pubkey,
 }
 
 #[event]
@@ -211,14 +1001,67 @@ pub struct WrapperTradeExecuted {
     pub platform_fee: u64,
     pub creator_fee: u64,
     pub trade_type: TradeType,
+    pub amount_out: u64,                 // Tokens on buys, SOL after fees on sells
+    pub fee_tier: Option<FeeTier>,       // Fee tier discount applied to the platform fee
+}
+
+#[event]
+pub struct FeeTiersQueued {
+    pub platform_token_mint: Pubkey,
+    pub fee_tiers: Vec<FeeTier>,
+    pub eta: i64,
+}
+
+#[event]
+pub struct FeeTierUpdateCancelled {
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct FeeTiersUpdated {
+    pub platform_token_mint: Pubkey,
+    pub fee_tiers: Vec<FeeTier>,
 }
 
 #[event]
 pub struct WrapperStatusUpdated {
     pub wrapper: Pubkey,
+    pub was_active: bool,
     pub is_active: bool,
 }
 
+#[event]
+pub struct FeeUpdateQueued {
+    pub wrapper: Pubkey,
+    pub old_platform_fee_bps: u16,
+    pub old_creator_fee_bps: u16,
+    pub new_platform_fee_bps: u16,
+    pub new_creator_fee_bps: u16,
+    pub eta: i64,
+}
+
+#[event]
+pub struct FeesUpdated {
+    pub wrapper: Pubkey,
+    pub old_platform_fee_bps: u16,
+    pub old_creator_fee_bps: u16,
+    pub new_platform_fee_bps: u16,
+    pub new_creator_fee_bps: u16,
+}
+
+#[event]
+pub struct FeeUpdateCancelled {
+    pub wrapper: Pubkey,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct WrapperMigrated {
+    pub wrapper: Pubkey,
+    pub token_mint: Pubkey,
+    pub previous_size: u32,
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Wrapper is inactive")]
@@ -227,4 +1070,242 @@ pub enum ErrorCode {
     Unauthorized,
     #[msg("Invalid fee percentage")]
     InvalidFeePercentage,
-}
\ No newline at end of file
+    #[msg("Slippage tolerance exceeded")]
+    SlippageExceeded,
+    #[msg("AMM program does not match the wrapper config")]
+    InvalidAmmProgram,
+    #[msg("Account is not a vault of the AMM pool")]
+    InvalidPoolVault,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("No fee update is queued")]
+    NoPendingFeeUpdate,
+    #[msg("Fee update timelock has not elapsed")]
+    FeeUpdateNotReady,
+    #[msg("Invalid fee tier")]
+    InvalidFeeTier,
+    #[msg("Stake is not in the platform token")]
+    InvalidStakeMint,
+    #[msg("Account is not a fee wrapper")]
+    InvalidWrapperAccount,
+    #[msg("Wrapper already uses the current layout")]
+    WrapperAlreadyMigrated,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapper(platform_fee_bps: u16, creator_fee_bps: u16) -> FeeWrapper {
+        FeeWrapper {
+            token_mint: Pubkey::new_unique(),
+            creator_wallet: Pubkey::new_unique(),
+            platform_fee_bps,
+            creator_fee_bps,
+            total_volume: 0,
+            total_fees_collected: 0,
+            creator_fees_earned: 0,
+            platform_fees_earned: 0,
+            is_active: true,
+            created_at: 0,
+            pending_platform_fee_bps: 0,
+            pending_creator_fee_bps: 0,
+            fee_update_eta: 0,
+            reserved: [0; FeeWrapper::RESERVED_BYTES],
+        }
+    }
+
+    #[test]
+    fn migrates_original_wrapper_layout() {
+        let legacy = FeeWrapperV0 {
+            token_mint: Pubkey::new_unique(),
+            creator_wallet: Pubkey::new_unique(),
+            platform_fee_bps: 100,
+            creator_fee_bps: 50,
+            total_volume: 9_000,
+            total_fees_collected: 135,
+            creator_fees_earned: 45,
+            platform_fees_earned: 90,
+            is_active: true,
+            created_at: 1_700_000_000,
+        };
+        let mut data = FeeWrapper::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + FeeWrapperV0::LEN);
+
+        let wrapper = upgrade_wrapper_data(&data).unwrap();
+        assert_eq!(
+            (wrapper.token_mint, wrapper.creator_wallet),
+            (legacy.token_mint, legacy.creator_wallet)
+        );
+        assert_eq!((wrapper.platform_fee_bps, wrapper.creator_fee_bps), (100, 50));
+        assert_eq!((wrapper.total_volume, wrapper.platform_fees_earned), (9_000, 90));
+        assert_eq!(wrapper.created_at, 1_700_000_000);
+        assert_eq!(wrapper.fee_update_eta, 0);
+
+        let mut migrated = Vec::new();
+        wrapper.try_serialize(&mut migrated).unwrap();
+        assert_eq!(migrated.len(), 8 + FeeWrapper::INIT_SPACE);
+        assert_eq!(
+            upgrade_wrapper_data(&migrated).err(),
+            Some(ErrorCode::WrapperAlreadyMigrated.into())
+        );
+
+        data[..8].copy_from_slice(&UserStats::DISCRIMINATOR);
+        assert_eq!(
+            upgrade_wrapper_data(&data).err(),
+            Some(ErrorCode::InvalidWrapperAccount.into())
+        );
+    }
+
+    #[test]
+    fn splits_fees_with_platform_discount() {
+        let wrapper = wrapper(100, 50);
+
+        assert_eq!(wrapper.split_fees(1_000_000, 0), (15_000, 10_000, 5_000));
+        // The discount only waives part of the platform fee
+        assert_eq!(wrapper.split_fees(1_000_000, 2500), (12_500, 7_500, 5_000));
+        assert_eq!(wrapper.split_fees(1_000_000, 10000), (5_000, 0, 5_000));
+        assert_eq!(wrapper.split_fees(u64::MAX, 0).1, (u64::MAX as u128 / 100) as u64);
+    }
+
+    #[test]
+    fn creator_can_only_cancel_creator_fee_changes() {
+        let mut wrapper = wrapper(100, 50);
+        assert!(!wrapper.changes_creator_fee());
+
+        wrapper.fee_update_eta = 1_000;
+        wrapper.pending_platform_fee_bps = 200;
+        wrapper.pending_creator_fee_bps = 50;
+        assert!(!wrapper.changes_creator_fee());
+
+        wrapper.pending_creator_fee_bps = 0;
+        assert!(wrapper.changes_creator_fee());
+    }
+
+    #[test]
+    fn caps_total_wrapper_fees() {
+        assert!(validate_fees(600, 400).is_ok());
+        assert!(validate_fees(0, FeeWrapper::MAX_TOTAL_FEE_BPS).is_ok());
+        assert!(validate_fees(600, 401).is_err());
+        assert!(validate_fees(u16::MAX, u16::MAX).is_err());
+    }
+
+    const POOL_SOL: u64 = 100_000_000_000;
+    const POOL_TOKENS: u64 = 1_000_000_000_000_000;
+
+    /// The AMM's swap math over a pool holding `POOL_SOL` and `POOL_TOKENS`
+    fn pool_swap(trade_type: &TradeType, amount_in: u64) -> Result<u64> {
+        let (reserve_in, reserve_out) = trade_type.route(POOL_SOL, POOL_TOKENS);
+        amm::swap_output(amount_in, reserve_in, reserve_out)
+    }
+
+    #[test]
+    fn routes_swaps_by_trade_direction() {
+        assert_eq!(TradeType::Buy.route("sol", "token"), ("sol", "token"));
+        assert_eq!(TradeType::Sell.route("sol", "token"), ("token", "sol"));
+    }
+
+    #[test]
+    fn buys_pay_fees_from_sol_in() {
+        let wrapper = wrapper(100, 50);
+        let buy = TradeType::Buy;
+        let fill = wrapper
+            .settle_trade(&buy, 1_000_000_000, 0, 0, |amount_in| pool_swap(&buy, amount_in))
+            .unwrap();
+
+        assert_eq!(fill.total_fees, 15_000_000);
+        assert_eq!((fill.platform_fee, fill.creator_fee), (10_000_000, 5_000_000));
+        assert_eq!(fill.sol_volume, 1_000_000_000);
+        assert_eq!(fill.trade_amount_after_fees, 985_000_000);
+        assert_eq!(fill.amount_out, pool_swap(&buy, 985_000_000).unwrap());
+        assert!(fill.amount_out < pool_swap(&buy, 1_000_000_000).unwrap());
+    }
+
+    #[test]
+    fn sells_pay_fees_from_sol_out() {
+        let wrapper = wrapper(100, 50);
+        let sell = TradeType::Sell;
+        let tokens_in = 10_000_000_000_000;
+        let sol_out = pool_swap(&sell, tokens_in).unwrap();
+        let fill = wrapper
+            .settle_trade(&sell, tokens_in, 0, 0, |amount_in| {
+                assert_eq!(amount_in, tokens_in);
+                pool_swap(&sell, amount_in)
+            })
+            .unwrap();
+
+        let (total_fees, _, _) = wrapper.split_fees(sol_out, 0);
+        assert_eq!(fill.sol_volume, sol_out);
+        assert_eq!(fill.total_fees, total_fees);
+        assert_eq!(fill.amount_out, sol_out - total_fees);
+        assert_eq!(fill.trade_amount_after_fees, fill.amount_out);
+    }
+
+    #[test]
+    fn checks_slippage_after_fees() {
+        let wrapper = wrapper(100, 50);
+        for (trade_type, amount) in [(TradeType::Buy, 1_000_000_000), (TradeType::Sell, 10_000_000_000_000)] {
+            let settle = |min_amount_out| {
+                wrapper.settle_trade(&trade_type, amount, min_amount_out, 0, |amount_in| {
+                    pool_swap(&trade_type, amount_in)
+                })
+            };
+            let amount_out = settle(0).unwrap().amount_out;
+
+            assert!(settle(amount_out).is_ok());
+            assert_eq!(settle(amount_out + 1).err(), Some(ErrorCode::SlippageExceeded.into()));
+            // The swap alone clears this minimum, the fees do not
+            let output_before_fees = match trade_type {
+                TradeType::Buy => pool_swap(&trade_type, amount).unwrap(),
+                TradeType::Sell => settle(0).unwrap().sol_volume,
+            };
+            assert_eq!(settle(output_before_fees).err(), Some(ErrorCode::SlippageExceeded.into()));
+        }
+    }
+
+    #[test]
+    fn rejects_vaults_outside_the_pool() {
+        let pool = Pool {
+            base_mint: Pubkey::new_unique(),
+            quote_mint: native_mint::ID,
+            base_vault: Pubkey::new_unique(),
+            quote_vault: Pubkey::new_unique(),
+            bump: 255,
+        };
+        let other_vault = Pubkey::new_unique();
+
+        assert!(check_pool_vaults(&pool, &pool.quote_vault, &pool.base_vault).is_ok());
+        for (sol_vault, token_vault) in [
+            (pool.base_vault, pool.quote_vault),
+            (other_vault, pool.base_vault),
+            (pool.quote_vault, other_vault),
+        ] {
+            assert_eq!(
+                check_pool_vaults(&pool, &sol_vault, &token_vault).err(),
+                Some(ErrorCode::InvalidPoolVault.into())
+            );
+        }
+    }
+
+    #[test]
+    fn user_stats_track_30_day_volume() {
+        let period = TradeStats::VOLUME_PERIOD_SECONDS;
+        let start = 5 * period;
+        let mut user_stats = UserStats {
+            user: Pubkey::new_unique(),
+            mint: Pubkey::default(),
+            stats: TradeStats::default(),
+            bump: 255,
+            reserved: [0; UserStats::RESERVED_BYTES],
+        };
+        user_stats.stats.record_trade(2_000, 2_000, 0, start).unwrap();
+        user_stats.stats.record_trade(1_000, 0, 990, start + period / 2).unwrap();
+
+        assert_eq!(user_stats.stats.volume_30d(start + period / 2), 3_000);
+        // A quarter of the first period's volume is still inside the window
+        assert_eq!(user_stats.stats.volume_30d(start + period + 3 * period / 4), 750);
+        assert_eq!(user_stats.stats.volume_30d(start + 2 * period), 0);
+        assert_eq!(user_stats.stats.volume, 3_000);
+    }
+}
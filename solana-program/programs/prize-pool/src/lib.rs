@@ -0,0 +1,443 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::keccak;
+use crate::program::PrizePool as PrizePoolProgram;
+
+declare_id!("5mzqR8sv55hdPyPLGBC8PAgt4m1LS2jYoAHLW226dRqH");
+
+#[program]
+pub mod prize_pool {
+    use super::*;
+
+    /// Initialize the prize pool PDA.
+    /// Set it as the bonding curve config's `prize_pool_wallet` so prize pool fees are paid here.
+    /// Only the program's upgrade authority can initialize; it becomes the pool authority.
+    pub fn initialize_pool(ctx: Context<InitializePool>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.authority = ctx.accounts.authority.key();
+        pool.next_epoch = 0;
+        pool.total_allocated = 0;
+        pool.total_claimed = 0;
+        pool.bump = ctx.bumps.pool;
+
+        emit!(PoolInitialized {
+            pool: pool.key(),
+            authority: pool.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Publish the Merkle root of an epoch's winners and reserve its prize amount.
+    /// The amount must be covered by the pool balance not reserved by open epochs,
+    /// which includes anything rolled over from closed ones.
+    pub fn publish_epoch(
+        ctx: Context<PublishEpoch>,
+        merkle_root: [u8; 32],
+        total_amount: u64,
+        claim_duration: i64,
+    ) -> Result<()> {
+        require!(total_amount > 0, PrizePoolError::InvalidAmount);
+        require!(claim_duration > 0, PrizePoolError::InvalidDuration);
+
+        let pool = &mut ctx.accounts.pool;
+        let available = pool.available_balance(&pool.to_account_info())?;
+        require!(total_amount <= available, PrizePoolError::InsufficientPoolBalance);
+
+        let now = Clock::get()?.unix_timestamp;
+        let epoch = &mut ctx.accounts.epoch;
+        epoch.epoch = pool.next_epoch;
+        epoch.merkle_root = merkle_root;
+        epoch.total_amount = total_amount;
+        epoch.claimed_amount = 0;
+        epoch.published_at = now;
+        epoch.claim_deadline = now + claim_duration;
+        epoch.is_closed = false;
+        epoch.bump = ctx.bumps.epoch;
+
+        pool.next_epoch += 1;
+        pool.total_allocated = pool
+            .total_allocated
+            .checked_add(total_amount)
+            .ok_or(PrizePoolError::MathOverflow)?;
+
+        emit!(EpochPublished {
+            epoch: epoch.epoch,
+            merkle_root,
+            total_amount,
+            claim_deadline: epoch.claim_deadline,
+        });
+
+        Ok(())
+    }
+
+    /// Claim a winner's share of an epoch with a Merkle proof.
+    /// Leaves are `keccak(index || winner || amount)`, pairs are hashed in sorted order.
+    /// Each leaf is claimed once, so a winner with several leaves claims each separately.
+    pub fn claim_prize(
+        ctx: Context<ClaimPrize>,
+        index: u64,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let epoch = &mut ctx.accounts.epoch;
+        let winner = ctx.accounts.winner.key();
+
+        require!(!epoch.is_closed, PrizePoolError::EpochClosed);
+        require!(
+            Clock::get()?.unix_timestamp <= epoch.claim_deadline,
+            PrizePoolError::ClaimWindowExpired
+        );
+
+        require!(
+            verify_proof(&proof, epoch.merkle_root, prize_leaf(index, &winner, amount)),
+            PrizePoolError::InvalidProof
+        );
+
+        let claimed_amount = epoch
+            .claimed_amount
+            .checked_add(amount)
+            .ok_or(PrizePoolError::MathOverflow)?;
+        require!(claimed_amount <= epoch.total_amount, PrizePoolError::InsufficientPoolBalance);
+        epoch.claimed_amount = claimed_amount;
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.epoch = epoch.epoch;
+        receipt.index = index;
+        receipt.winner = winner;
+        receipt.amount = amount;
+        receipt.bump = ctx.bumps.receipt;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.total_allocated -= amount;
+        pool.total_claimed += amount;
+
+        **pool.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.winner.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(PrizeClaimed {
+            epoch: epoch.epoch,
+            winner,
+            index,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Close an epoch after its claim window and roll its unclaimed amount back into the pool.
+    /// Permissionless: the released amount only becomes available to future epochs.
+    pub fn close_epoch(ctx: Context<CloseEpoch>) -> Result<()> {
+        let epoch = &mut ctx.accounts.epoch;
+
+        require!(!epoch.is_closed, PrizePoolError::EpochClosed);
+        require!(
+            Clock::get()?.unix_timestamp > epoch.claim_deadline,
+            PrizePoolError::ClaimWindowOpen
+        );
+
+        let rolled_over = epoch.total_amount - epoch.claimed_amount;
+        epoch.is_closed = true;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.total_allocated -= rolled_over;
+
+        emit!(EpochClosed {
+            epoch: epoch.epoch,
+            claimed_amount: epoch.claimed_amount,
+            rolled_over,
+        });
+
+        Ok(())
+    }
+}
+
+/// Merkle leaf for a winner's prize: `keccak(index || winner || amount)`, integers little-endian
+fn prize_leaf(index: u64, winner: &Pubkey, amount: u64) -> [u8; 32] {
+    keccak::hashv(&[&index.to_le_bytes(), winner.as_ref(), &amount.to_le_bytes()]).0
+}
+
+/// Verify a Merkle proof built with sorted-pair keccak hashing
+fn verify_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).0
+        } else {
+            keccak::hashv(&[sibling, &node]).0
+        }
+    });
+    computed == root
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PrizePool::INIT_SPACE,
+        seeds = [b"prize_pool"],
+        bump
+    )]
+    pub pool: Account<'info, PrizePool>,
+
+    #[account(
+        constraint = prize_pool_program.programdata_address()? == Some(program_data.key())
+            @ PrizePoolError::Unauthorized
+    )]
+    pub prize_pool_program: Program<'info, PrizePoolProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ PrizePoolError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PublishEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [b"prize_pool"],
+        bump = pool.bump,
+        has_one = authority @ PrizePoolError::Unauthorized
+    )]
+    pub pool: Account<'info, PrizePool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Epoch::INIT_SPACE,
+        seeds = [b"epoch", pool.next_epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub epoch: Account<'info, Epoch>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ClaimPrize<'info> {
+    #[account(
+        mut,
+        seeds = [b"prize_pool"],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, PrizePool>,
+
+    #[account(
+        mut,
+        seeds = [b"epoch", epoch.epoch.to_le_bytes().as_ref()],
+        bump = epoch.bump
+    )]
+    pub epoch: Account<'info, Epoch>,
+
+    #[account(
+        init,
+        payer = winner,
+        space = 8 + ClaimReceipt::INIT_SPACE,
+        seeds = [b"claim", epoch.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub receipt: Account<'info, ClaimReceipt>,
+
+    #[account(mut)]
+    pub winner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [b"prize_pool"],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, PrizePool>,
+
+    #[account(
+        mut,
+        seeds = [b"epoch", epoch.epoch.to_le_bytes().as_ref()],
+        bump = epoch.bump
+    )]
+    pub epoch: Account<'info, Epoch>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PrizePool {
+    pub authority: Pubkey,
+    pub next_epoch: u64,
+    pub total_allocated: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+impl PrizePool {
+    /// Lamports above rent that are not reserved by an open epoch
+    pub fn available_balance(&self, info: &AccountInfo) -> Result<u64> {
+        let rent = Rent::get()?.minimum_balance(info.data_len());
+        Ok(info
+            .lamports()
+            .saturating_sub(rent)
+            .saturating_sub(self.total_allocated))
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Epoch {
+    pub epoch: u64,
+    pub merkle_root: [u8; 32],
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub published_at: i64,
+    pub claim_deadline: i64,
+    pub is_closed: bool,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimReceipt {
+    pub epoch: u64,
+    pub index: u64,
+    pub winner: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct EpochPublished {
+    pub epoch: u64,
+    pub merkle_root: [u8; 32],
+    pub total_amount: u64,
+    pub claim_deadline: i64,
+}
+
+#[event]
+pub struct PrizeClaimed {
+    pub epoch: u64,
+    pub winner: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct EpochClosed {
+    pub epoch: u64,
+    pub claimed_amount: u64,
+    pub rolled_over: u64,
+}
+
+#[error_code]
+pub enum PrizePoolError {
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Claim duration must be greater than zero")]
+    InvalidDuration,
+    #[msg("Insufficient unallocated balance in prize pool")]
+    InsufficientPoolBalance,
+    #[msg("Invalid Merkle proof")]
+    InvalidProof,
+    #[msg("Epoch is closed")]
+    EpochClosed,
+    #[msg("Claim window has expired")]
+    ClaimWindowExpired,
+    #[msg("Claim window is still open")]
+    ClaimWindowOpen,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[&a, &b]).0
+        } else {
+            keccak::hashv(&[&b, &a]).0
+        }
+    }
+
+    /// Three winners: root = H(H(l0, l1), l2)
+    fn winners() -> (Vec<(u64, Pubkey, u64)>, [u8; 32]) {
+        let winners = vec![
+            (0, Pubkey::new_unique(), 1_000),
+            (1, Pubkey::new_unique(), 2_000),
+            (2, Pubkey::new_unique(), 3_000),
+        ];
+        let leaves: Vec<_> = winners
+            .iter()
+            .map(|(index, winner, amount)| prize_leaf(*index, winner, *amount))
+            .collect();
+        let root = hash_pair(hash_pair(leaves[0], leaves[1]), leaves[2]);
+        (winners, root)
+    }
+
+    #[test]
+    fn leaf_is_keccak_of_index_winner_amount() {
+        let winner = Pubkey::new_unique();
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&7u64.to_le_bytes());
+        preimage.extend_from_slice(winner.as_ref());
+        preimage.extend_from_slice(&500u64.to_le_bytes());
+
+        assert_eq!(prize_leaf(7, &winner, 500), keccak::hash(&preimage).0);
+    }
+
+    #[test]
+    fn accepts_valid_proofs() {
+        let (winners, root) = winners();
+        let leaves: Vec<_> = winners
+            .iter()
+            .map(|(index, winner, amount)| prize_leaf(*index, winner, *amount))
+            .collect();
+
+        assert!(verify_proof(&[leaves[1], leaves[2]], root, leaves[0]));
+        assert!(verify_proof(&[leaves[0], leaves[2]], root, leaves[1]));
+        assert!(verify_proof(&[hash_pair(leaves[0], leaves[1])], root, leaves[2]));
+    }
+
+    #[test]
+    fn rejects_wrong_amount() {
+        let (winners, root) = winners();
+        let (index, winner, amount) = winners[0];
+        let proof = [
+            prize_leaf(1, &winners[1].1, winners[1].2),
+            prize_leaf(2, &winners[2].1, winners[2].2),
+        ];
+
+        assert!(verify_proof(&proof, root, prize_leaf(index, &winner, amount)));
+        assert!(!verify_proof(&proof, root, prize_leaf(index, &winner, amount + 1)));
+    }
+
+    #[test]
+    fn rejects_siblings_out_of_order() {
+        let (winners, root) = winners();
+        let leaves: Vec<_> = winners
+            .iter()
+            .map(|(index, winner, amount)| prize_leaf(*index, winner, *amount))
+            .collect();
+
+        assert!(!verify_proof(&[leaves[2], leaves[1]], root, leaves[0]));
+    }
+}
@@ -0,0 +1,610 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use crate::program::Staking as StakingProgram;
+
+declare_id!("3q8qEWD5vekupq4qfSzq4Ud4zUtbyCr4cZtGR67wQ28g");
+
+#[program]
+pub mod staking {
+    use super::*;
+
+    /// Initialize the stake pool for the platform token.
+    /// Set the pool PDA as the bonding curve config's `platform_wallet` so platform fee claims
+    /// are paid here and streamed to stakers; reserves go to the treasury vault.
+    /// Unstaked tokens can be withdrawn `cooldown_seconds` after unstaking.
+    /// Only the program's upgrade authority can initialize.
+    pub fn initialize_pool(ctx: Context<InitializePool>, cooldown_seconds: i64) -> Result<()> {
+        require!(
+            (StakePool::MIN_COOLDOWN_SECONDS..=StakePool::MAX_COOLDOWN_SECONDS)
+                .contains(&cooldown_seconds),
+            StakingError::InvalidCooldown
+        );
+
+        let pool = &mut ctx.accounts.pool;
+        pool.stake_mint = ctx.accounts.stake_mint.key();
+        pool.stake_vault = ctx.accounts.stake_vault.key();
+        pool.cooldown_seconds = cooldown_seconds;
+        pool.total_staked = 0;
+        pool.reward_per_share = 0;
+        pool.reward_rate = 0;
+        pool.reward_period_end = 0;
+        pool.last_update_at = Clock::get()?.unix_timestamp;
+        pool.total_funded = 0;
+        pool.total_distributed = 0;
+        pool.total_claimed = 0;
+        pool.bump = ctx.bumps.pool;
+
+        emit!(PoolInitialized {
+            pool: pool.key(),
+            stake_mint: pool.stake_mint,
+            cooldown_seconds,
+        });
+
+        Ok(())
+    }
+
+    /// Stake platform tokens. Rewards start accruing from now, from whatever is still
+    /// streaming; SOL streamed before the stake is not shared with it.
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        let pool = &mut ctx.accounts.pool;
+        update_rewards(pool)?;
+
+        let position = &mut ctx.accounts.position;
+        if position.owner == Pubkey::default() {
+            position.owner = ctx.accounts.owner.key();
+            position.stake_mint = pool.stake_mint;
+            position.bump = ctx.bumps.position;
+        }
+        position.settle(pool)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.owner_token_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new(cpi_program, cpi_accounts), amount)?;
+
+        position.amount = position.amount.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+        pool.total_staked = pool.total_staked.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+
+        emit!(Staked {
+            owner: position.owner,
+            amount,
+            total_staked: position.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Stop `amount` of a stake from earning rewards and start its cooldown.
+    /// Unstaking again restarts the cooldown for everything still cooling down.
+    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        let pool = &mut ctx.accounts.pool;
+        update_rewards(pool)?;
+
+        let position = &mut ctx.accounts.position;
+        require!(amount <= position.amount, StakingError::InsufficientStake);
+        position.settle(pool)?;
+
+        position.amount -= amount;
+        pool.total_staked -= amount;
+        position.unstaking_amount += amount;
+        position.unstake_available_at = Clock::get()?.unix_timestamp + pool.cooldown_seconds;
+
+        emit!(UnstakeRequested {
+            owner: position.owner,
+            amount,
+            available_at: position.unstake_available_at,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw unstaked tokens once their cooldown has passed
+    pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let amount = position.unstaking_amount;
+        require!(amount > 0, StakingError::NothingToWithdraw);
+        require!(
+            Clock::get()?.unix_timestamp >= position.unstake_available_at,
+            StakingError::CooldownActive
+        );
+        position.unstaking_amount = 0;
+
+        let pool = &ctx.accounts.pool;
+        let seeds = &[b"stake_pool".as_ref(), &[pool.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.owner_token_account.to_account_info(),
+            authority: pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer), amount)?;
+
+        emit!(Withdrawn {
+            owner: position.owner,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Claim a position's accrued share of the pool's fee revenue, in SOL
+    pub fn claim(ctx: Context<Claim>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        update_rewards(pool)?;
+
+        let position = &mut ctx.accounts.position;
+        position.settle(pool)?;
+
+        // Per-position rounding can leave a claim a lamport above what the pool holds
+        let info = pool.to_account_info();
+        let balance = info
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(info.data_len()));
+        let amount = position.rewards_pending.min(balance);
+        require!(amount > 0, StakingError::NothingToClaim);
+        position.rewards_pending -= amount;
+        pool.total_claimed += amount;
+
+        **info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.owner.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(RewardsClaimed {
+            owner: position.owner,
+            amount,
+        });
+
+        Ok(())
+    }
+}
+
+/// Credit stakers with the rewards streamed since the last update, then stream any SOL paid
+/// into the pool since then
+fn update_rewards(pool: &mut Account<StakePool>) -> Result<()> {
+    let info = pool.to_account_info();
+    let balance = info
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(info.data_len()));
+    let amount = pool
+        .update_rewards(balance, Clock::get()?.unix_timestamp)
+        .ok_or(StakingError::MathOverflow)?;
+    if amount > 0 {
+        emit!(RewardsFunded {
+            amount,
+            reward_rate: pool.reward_rate,
+            reward_period_end: pool.reward_period_end,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakePool::INIT_SPACE,
+        seeds = [b"stake_pool"],
+        bump
+    )]
+    pub pool: Account<'info, StakePool>,
+
+    pub stake_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"stake_vault"],
+        bump,
+        token::mint = stake_mint,
+        token::authority = pool
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        constraint = staking_program.programdata_address()? == Some(program_data.key())
+            @ StakingError::Unauthorized
+    )]
+    pub staking_program: Program<'info, StakingProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ StakingError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_pool"],
+        bump = pool.bump,
+        has_one = stake_vault
+    )]
+    pub pool: Account<'info, StakePool>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = pool.stake_mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_pool"],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakePool>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        seeds = [b"stake_pool"],
+        bump = pool.bump,
+        has_one = stake_vault
+    )]
+    pub pool: Account<'info, StakePool>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = pool.stake_mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_pool"],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, StakePool>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner
+    )]
+    pub position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct StakePool {
+    pub stake_mint: Pubkey,               // Platform token
+    pub stake_vault: Pubkey,
+    pub cooldown_seconds: i64,            // Wait between unstaking and withdrawing
+    pub total_staked: u64,                // Stake earning rewards, excluding tokens cooling down
+    pub reward_per_share: u128,           // Cumulative lamports per staked unit, Q64.64, wrapping
+    pub reward_rate: u128,                // Lamports streamed per second, Q64.64
+    pub reward_period_end: i64,           // When the current stream runs out
+    pub last_update_at: i64,              // When rewards were last credited
+    pub total_funded: u64,                // Lamports taken into reward streams
+    pub total_distributed: u64,           // Lamports credited to stakers
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+impl StakePool {
+    pub const MIN_COOLDOWN_SECONDS: i64 = 24 * 60 * 60;
+    pub const MAX_COOLDOWN_SECONDS: i64 = 30 * 24 * 60 * 60;
+    pub const REWARD_DURATION_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+    /// Credit stakers with the rewards streamed up to `now`, then add whatever part of
+    /// `balance` (lamports above rent) is new to the stream, spreading it and what is left of
+    /// the current stream over the next `REWARD_DURATION_SECONDS`. The stream pauses while
+    /// nothing is staked. Returns the lamports newly streamed, or `None` on overflow.
+    pub fn update_rewards(&mut self, balance: u64, now: i64) -> Option<u64> {
+        let streamed_until = now.min(self.reward_period_end);
+        if streamed_until > self.last_update_at {
+            if self.total_staked == 0 {
+                self.reward_period_end = now + (self.reward_period_end - self.last_update_at);
+            } else {
+                let elapsed = (streamed_until - self.last_update_at) as u128;
+                let increment = self.reward_rate.checked_mul(elapsed)? / self.total_staked as u128;
+                let amount = u64::try_from((increment * self.total_staked as u128) >> 64).ok()?;
+                self.reward_per_share = self.reward_per_share.wrapping_add(increment);
+                self.total_distributed = self.total_distributed.checked_add(amount)?;
+            }
+        }
+        self.last_update_at = now;
+        if now >= self.reward_period_end {
+            // Rounding dust left when a stream runs out is streamed again with the next funds
+            self.reward_rate = 0;
+            self.total_funded = self.total_distributed;
+        }
+
+        let incoming = balance.saturating_sub(self.total_funded - self.total_claimed);
+        if incoming > 0 {
+            let remaining = self
+                .reward_rate
+                .checked_mul(self.reward_period_end.saturating_sub(now) as u128)?;
+            let total = remaining.checked_add((incoming as u128) << 64)?;
+            self.reward_rate = total / Self::REWARD_DURATION_SECONDS as u128;
+            self.reward_period_end = now + Self::REWARD_DURATION_SECONDS;
+            self.total_funded = self.total_funded.checked_add(incoming)?;
+        }
+        Some(incoming)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct StakePosition {
+    pub owner: Pubkey,
+    pub stake_mint: Pubkey,               // Token staked, the pool's stake mint
+    pub amount: u64,                      // Stake earning rewards
+    pub reward_per_share_paid: u128,      // Pool accumulator when rewards were last settled
+    pub rewards_pending: u64,             // Settled lamports not yet claimed
+    pub unstaking_amount: u64,            // Unstaked tokens cooling down
+    pub unstake_available_at: i64,
+    pub bump: u8,
+}
+
+impl StakePosition {
+    /// Move rewards accrued since the last settlement into `rewards_pending`.
+    /// Must run before the position's stake changes.
+    pub fn settle(&mut self, pool: &StakePool) -> Result<()> {
+        let delta = pool.reward_per_share.wrapping_sub(self.reward_per_share_paid);
+        let accrued = u64::try_from((self.amount as u128 * delta) >> 64)
+            .map_err(|_| StakingError::MathOverflow)?;
+        self.rewards_pending = self
+            .rewards_pending
+            .checked_add(accrued)
+            .ok_or(StakingError::MathOverflow)?;
+        self.reward_per_share_paid = pool.reward_per_share;
+        Ok(())
+    }
+}
+
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub stake_mint: Pubkey,
+    pub cooldown_seconds: i64,
+}
+
+#[event]
+pub struct RewardsFunded {
+    pub amount: u64,
+    pub reward_rate: u128,
+    pub reward_period_end: i64,
+}
+
+#[event]
+pub struct Staked {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,                // Owner's stake after this deposit
+}
+
+#[event]
+pub struct UnstakeRequested {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub available_at: i64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[error_code]
+pub enum StakingError {
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Cooldown is out of bounds")]
+    InvalidCooldown,
+    #[msg("Amount exceeds staked balance")]
+    InsufficientStake,
+    #[msg("Nothing to withdraw")]
+    NothingToWithdraw,
+    #[msg("Unstake cooldown has not elapsed")]
+    CooldownActive,
+    #[msg("No rewards to claim")]
+    NothingToClaim,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Unauthorized")]
+    Unauthorized,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DURATION: i64 = StakePool::REWARD_DURATION_SECONDS;
+
+    /// Streams round down by a few lamports
+    fn assert_close(actual: u64, expected: u64) {
+        assert!(actual <= expected && expected - actual <= 2, "{actual} != {expected}");
+    }
+
+    fn pool(total_staked: u64) -> StakePool {
+        StakePool {
+            stake_mint: Pubkey::new_unique(),
+            stake_vault: Pubkey::new_unique(),
+            cooldown_seconds: StakePool::MIN_COOLDOWN_SECONDS,
+            total_staked,
+            reward_per_share: 0,
+            reward_rate: 0,
+            reward_period_end: 0,
+            last_update_at: 0,
+            total_funded: 0,
+            total_distributed: 0,
+            total_claimed: 0,
+            bump: 255,
+        }
+    }
+
+    fn position(amount: u64) -> StakePosition {
+        StakePosition {
+            owner: Pubkey::new_unique(),
+            stake_mint: Pubkey::new_unique(),
+            amount,
+            reward_per_share_paid: 0,
+            rewards_pending: 0,
+            unstaking_amount: 0,
+            unstake_available_at: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn streams_rewards_pro_rata() {
+        let mut pool = pool(4_000);
+        let mut alice = position(1_000);
+        let mut bob = position(3_000);
+
+        assert_eq!(pool.update_rewards(1_000_000, 0), Some(1_000_000));
+        // Already funded lamports are not streamed twice
+        assert_eq!(pool.update_rewards(1_000_000, DURATION / 2), Some(0));
+        assert_close(pool.total_distributed, 500_000);
+
+        pool.update_rewards(1_000_000, 2 * DURATION).unwrap();
+        alice.settle(&pool).unwrap();
+        bob.settle(&pool).unwrap();
+        assert_close(pool.total_distributed, 1_000_000);
+        assert_close(alice.rewards_pending, 250_000);
+        assert_close(bob.rewards_pending, 750_000);
+    }
+
+    #[test]
+    fn new_stake_only_earns_later_rewards() {
+        let mut pool = pool(1_000);
+        let mut alice = position(1_000);
+        pool.update_rewards(700_000, 0).unwrap();
+
+        // Bob joins halfway through the stream, settling at the current accumulator
+        pool.update_rewards(700_000, DURATION / 2).unwrap();
+        let mut bob = position(0);
+        bob.settle(&pool).unwrap();
+        bob.amount = 1_000;
+        pool.total_staked += 1_000;
+        pool.update_rewards(700_000, DURATION).unwrap();
+
+        alice.settle(&pool).unwrap();
+        bob.settle(&pool).unwrap();
+        assert_close(alice.rewards_pending, 525_000);
+        assert_close(bob.rewards_pending, 175_000);
+    }
+
+    #[test]
+    fn stake_made_just_before_funding_earns_nothing_upfront() {
+        // A staker joins, then fees are claimed into the pool in the same second
+        let mut pool = pool(1_000);
+        let mut sniper = position(1_000);
+        pool.update_rewards(1_000_000, 100).unwrap();
+
+        pool.update_rewards(1_000_000, 100).unwrap();
+        sniper.settle(&pool).unwrap();
+        assert_eq!(sniper.rewards_pending, 0);
+
+        // Leaving after the minimum cooldown forfeits most of the stream
+        pool.update_rewards(1_000_000, 100 + StakePool::MIN_COOLDOWN_SECONDS).unwrap();
+        sniper.settle(&pool).unwrap();
+        assert!(sniper.rewards_pending <= 1_000_000 / 7);
+    }
+
+    #[test]
+    fn top_up_restreams_what_is_left() {
+        let mut pool = pool(1_000);
+        pool.update_rewards(700_000, 0).unwrap();
+        pool.update_rewards(1_000_000, DURATION / 2).unwrap();
+
+        // 350_000 left of the first stream plus the 300_000 top-up, over a fresh period
+        assert_eq!(pool.reward_period_end, DURATION / 2 + DURATION);
+        pool.update_rewards(1_000_000, 2 * DURATION).unwrap();
+        assert_close(pool.total_distributed, 1_000_000);
+    }
+
+    #[test]
+    fn stream_pauses_while_nothing_is_staked() {
+        let mut pool = pool(0);
+        pool.update_rewards(1_000_000, 0).unwrap();
+        pool.update_rewards(1_000_000, DURATION).unwrap();
+        assert_eq!(pool.total_distributed, 0);
+        assert_eq!(pool.reward_period_end, 2 * DURATION);
+
+        pool.total_staked = 1_000;
+        pool.update_rewards(1_000_000, 3 * DURATION).unwrap();
+        assert_close(pool.total_distributed, 1_000_000);
+    }
+}
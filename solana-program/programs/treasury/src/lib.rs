@@ -0,0 +1,623 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use crate::program::Treasury as TreasuryProgram;
+
+declare_id!("pyGZBCEAqoBEsmsKfGW2MveC4V6JF21QymwCAqhS2Aa");
+
+#[program]
+pub mod treasury {
+    use super::*;
+
+    /// Initialize the treasury with its approver set, threshold and timelock.
+    /// Set the vault PDA as the bonding curve config's `reserves_wallet`; tokens swept there
+    /// land in the vault's associated token accounts. Only the program's upgrade authority
+    /// can initialize.
+    pub fn initialize_treasury(
+        ctx: Context<InitializeTreasury>,
+        approvers: Vec<Pubkey>,
+        threshold: u8,
+        timelock_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            !approvers.is_empty() && approvers.len() <= Treasury::MAX_APPROVERS,
+            TreasuryError::InvalidApproverSet
+        );
+        require!(
+            threshold > 0 && threshold as usize <= approvers.len(),
+            TreasuryError::InvalidThreshold
+        );
+        require!(
+            (Treasury::MIN_TIMELOCK_SECONDS..=Treasury::MAX_TIMELOCK_SECONDS)
+                .contains(&timelock_seconds),
+            TreasuryError::InvalidTimelock
+        );
+        for (i, approver) in approvers.iter().enumerate() {
+            require!(!approvers[..i].contains(approver), TreasuryError::InvalidApproverSet);
+        }
+
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.approvers = approvers;
+        treasury.threshold = threshold;
+        treasury.timelock_seconds = timelock_seconds;
+        treasury.proposal_count = 0;
+        treasury.total_withdrawn = 0;
+        treasury.bump = ctx.bumps.treasury;
+        treasury.vault_bump = ctx.bumps.vault;
+
+        emit!(TreasuryInitialized {
+            vault: ctx.accounts.vault.key(),
+            approvers: treasury.approvers.clone(),
+            threshold,
+            timelock_seconds,
+        });
+
+        Ok(())
+    }
+
+    /// Propose a withdrawal from the reserves vault: SOL when `mint` is `None`, otherwise
+    /// tokens from the vault's associated token account for `mint`.
+    /// The proposer's approval is recorded.
+    pub fn propose_withdrawal(
+        ctx: Context<ProposeWithdrawal>,
+        recipient: Pubkey,
+        mint: Option<Pubkey>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, TreasuryError::InvalidAmount);
+
+        let treasury = &mut ctx.accounts.treasury;
+        let proposer = ctx.accounts.proposer.key();
+        let approver_index = treasury.approver_index(&proposer)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.id = treasury.proposal_count;
+        proposal.proposer = proposer;
+        proposal.recipient = recipient;
+        proposal.mint = mint;
+        proposal.amount = amount;
+        proposal.approvals = 0;
+        let now = Clock::get()?.unix_timestamp;
+        proposal.created_at = now;
+        proposal.executable_at = 0;
+        proposal.status = ProposalStatus::Pending;
+        proposal.bump = ctx.bumps.proposal;
+
+        treasury.proposal_count += 1;
+
+        emit!(WithdrawalProposed {
+            proposal: proposal.id,
+            proposer,
+            recipient,
+            mint,
+            amount,
+        });
+
+        record_approval(treasury, proposal, approver_index, proposer, now)
+    }
+
+    /// Approve a pending withdrawal. Reaching the threshold starts the timelock.
+    pub fn approve_withdrawal(ctx: Context<ApproveWithdrawal>) -> Result<()> {
+        let treasury = &ctx.accounts.treasury;
+        let approver = ctx.accounts.approver.key();
+        let approver_index = treasury.approver_index(&approver)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        record_approval(treasury, &mut ctx.accounts.proposal, approver_index, approver, now)
+    }
+
+    /// Execute an approved SOL withdrawal once its timelock has elapsed. Permissionless.
+    pub fn execute_withdrawal(ctx: Context<ExecuteWithdrawal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(proposal.mint.is_none(), TreasuryError::InvalidMint);
+        proposal.check_executable(Clock::get()?.unix_timestamp)?;
+
+        let vault = &ctx.accounts.vault;
+        let rent = Rent::get()?.minimum_balance(0);
+        require!(
+            proposal.amount <= vault.lamports().saturating_sub(rent),
+            TreasuryError::InsufficientFunds
+        );
+
+        proposal.status = ProposalStatus::Executed;
+
+        let treasury = &mut ctx.accounts.treasury;
+        let seeds = &[b"reserves_vault".as_ref(), &[treasury.vault_bump]];
+        let signer = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: vault.to_account_info(),
+                to: ctx.accounts.recipient.to_account_info(),
+            },
+            signer,
+        );
+        system_program::transfer(cpi_ctx, proposal.amount)?;
+
+        treasury.total_withdrawn += proposal.amount;
+
+        emit!(WithdrawalExecuted {
+            proposal: proposal.id,
+            recipient: proposal.recipient,
+            mint: None,
+            amount: proposal.amount,
+            executor: ctx.accounts.executor.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Execute an approved token withdrawal once its timelock has elapsed, paying the
+    /// recipient's token account from the vault's associated token account. Permissionless.
+    pub fn execute_token_withdrawal(ctx: Context<ExecuteTokenWithdrawal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(
+            proposal.mint == Some(ctx.accounts.mint.key()),
+            TreasuryError::InvalidMint
+        );
+        proposal.check_executable(Clock::get()?.unix_timestamp)?;
+        require!(
+            proposal.amount <= ctx.accounts.vault_token_account.amount,
+            TreasuryError::InsufficientFunds
+        );
+
+        proposal.status = ProposalStatus::Executed;
+
+        let seeds = &[b"reserves_vault".as_ref(), &[ctx.accounts.treasury.vault_bump]];
+        let signer = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: ctx.accounts.vault_token_account.to_account_info(),
+                to: ctx.accounts.recipient_token_account.to_account_info(),
+                authority: ctx.accounts.vault.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(cpi_ctx, proposal.amount)?;
+
+        emit!(WithdrawalExecuted {
+            proposal: proposal.id,
+            recipient: proposal.recipient,
+            mint: proposal.mint,
+            amount: proposal.amount,
+            executor: ctx.accounts.executor.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a withdrawal that has not been executed. Any approver may cancel.
+    pub fn cancel_withdrawal(ctx: Context<ApproveWithdrawal>) -> Result<()> {
+        let approver = ctx.accounts.approver.key();
+        ctx.accounts.treasury.approver_index(&approver)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.cancel()?;
+
+        emit!(WithdrawalCancelled {
+            proposal: proposal.id,
+            cancelled_by: approver,
+        });
+
+        Ok(())
+    }
+}
+
+fn record_approval(
+    treasury: &Treasury,
+    proposal: &mut Proposal,
+    approver_index: usize,
+    approver: Pubkey,
+    now: i64,
+) -> Result<()> {
+    require!(proposal.status == ProposalStatus::Pending, TreasuryError::ProposalNotOpen);
+
+    let bit = 1u16 << approver_index;
+    require!(proposal.approvals & bit == 0, TreasuryError::AlreadyApproved);
+    proposal.approvals |= bit;
+
+    let approval_count = proposal.approvals.count_ones() as u8;
+    if approval_count >= treasury.threshold {
+        proposal.status = ProposalStatus::Approved;
+        proposal.executable_at = now + treasury.timelock_seconds;
+    }
+
+    emit!(WithdrawalApproved {
+        proposal: proposal.id,
+        approver,
+        approval_count,
+        executable_at: proposal.executable_at,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Treasury::INIT_SPACE,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    /// System-owned PDA holding the reserves
+    #[account(seeds = [b"reserves_vault"], bump)]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        constraint = treasury_program.programdata_address()? == Some(program_data.key())
+            @ TreasuryError::Unauthorized
+    )]
+    pub treasury_program: Program<'info, TreasuryProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+            @ TreasuryError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeWithdrawal<'info> {
+    #[account(mut, seeds = [b"treasury"], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [b"proposal", treasury.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveWithdrawal<'info> {
+    #[account(seeds = [b"treasury"], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub approver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteWithdrawal<'info> {
+    #[account(mut, seeds = [b"treasury"], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut, seeds = [b"reserves_vault"], bump = treasury.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Withdrawal recipient, bound to the proposal
+    #[account(mut, address = proposal.recipient @ TreasuryError::InvalidRecipient)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub executor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTokenWithdrawal<'info> {
+    #[account(seeds = [b"treasury"], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(seeds = [b"reserves_vault"], bump = treasury.vault_bump)]
+    pub vault: SystemAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        constraint = recipient_token_account.owner == proposal.recipient
+            @ TreasuryError::InvalidRecipient
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub executor: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Treasury {
+    #[max_len(16)]
+    pub approvers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timelock_seconds: i64,
+    pub proposal_count: u64,
+    /// Lamports withdrawn; token withdrawals are only recorded by their `WithdrawalExecuted` events
+    pub total_withdrawn: u64,
+    pub bump: u8,
+    pub vault_bump: u8,
+}
+
+impl Treasury {
+    // Approvals are tracked as a u16 bitmask over approver indices
+    pub const MAX_APPROVERS: usize = 16;
+    pub const MIN_TIMELOCK_SECONDS: i64 = 24 * 60 * 60;
+    pub const MAX_TIMELOCK_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+    pub fn approver_index(&self, key: &Pubkey) -> Result<usize> {
+        self.approvers
+            .iter()
+            .position(|approver| approver == key)
+            .ok_or_else(|| error!(TreasuryError::NotAnApprover))
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub recipient: Pubkey,
+    /// `None` for SOL, otherwise the mint of the tokens withdrawn
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+    pub approvals: u16,
+    pub created_at: i64,
+    pub executable_at: i64,
+    pub status: ProposalStatus,
+    pub bump: u8,
+}
+
+impl Proposal {
+    pub fn check_executable(&self, now: i64) -> Result<()> {
+        require!(self.status == ProposalStatus::Approved, TreasuryError::NotApproved);
+        require!(now >= self.executable_at, TreasuryError::TimelockActive);
+        Ok(())
+    }
+
+    pub fn cancel(&mut self) -> Result<()> {
+        require!(
+            self.status == ProposalStatus::Pending || self.status == ProposalStatus::Approved,
+            TreasuryError::ProposalNotOpen
+        );
+        self.status = ProposalStatus::Cancelled;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProposalStatus {
+    Pending,
+    Approved,
+    Executed,
+    Cancelled,
+}
+
+#[event]
+pub struct TreasuryInitialized {
+    pub vault: Pubkey,
+    pub approvers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timelock_seconds: i64,
+}
+
+#[event]
+pub struct WithdrawalProposed {
+    pub proposal: u64,
+    pub proposer: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+}
+
+#[event]
+pub struct WithdrawalApproved {
+    pub proposal: u64,
+    pub approver: Pubkey,
+    pub approval_count: u8,
+    pub executable_at: i64,
+}
+
+#[event]
+pub struct WithdrawalExecuted {
+    pub proposal: u64,
+    pub recipient: Pubkey,
+    pub mint: Option<Pubkey>,
+    pub amount: u64,
+    pub executor: Pubkey,
+}
+
+#[event]
+pub struct WithdrawalCancelled {
+    pub proposal: u64,
+    pub cancelled_by: Pubkey,
+}
+
+#[error_code]
+pub enum TreasuryError {
+    #[msg("Approver set must be non-empty, unique and at most 16 keys")]
+    InvalidApproverSet,
+    #[msg("Threshold must be between 1 and the number of approvers")]
+    InvalidThreshold,
+    #[msg("Timelock must be between 1 and 30 days")]
+    InvalidTimelock,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Signer is not an approver")]
+    NotAnApprover,
+    #[msg("Approver has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Proposal is not open")]
+    ProposalNotOpen,
+    #[msg("Proposal has not reached the approval threshold")]
+    NotApproved,
+    #[msg("Timelock has not elapsed")]
+    TimelockActive,
+    #[msg("Insufficient funds in reserves vault")]
+    InsufficientFunds,
+    #[msg("Recipient does not match proposal")]
+    InvalidRecipient,
+    #[msg("Mint does not match proposal")]
+    InvalidMint,
+    #[msg("Unauthorized")]
+    Unauthorized,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn treasury(approvers: usize, threshold: u8) -> Treasury {
+        Treasury {
+            approvers: (0..approvers).map(|_| Pubkey::new_unique()).collect(),
+            threshold,
+            timelock_seconds: Treasury::MIN_TIMELOCK_SECONDS,
+            proposal_count: 0,
+            total_withdrawn: 0,
+            bump: 0,
+            vault_bump: 0,
+        }
+    }
+
+    fn proposal() -> Proposal {
+        Proposal {
+            id: 0,
+            proposer: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            mint: None,
+            amount: 1_000,
+            approvals: 0,
+            created_at: 0,
+            executable_at: 0,
+            status: ProposalStatus::Pending,
+            bump: 0,
+        }
+    }
+
+    fn approve(treasury: &Treasury, proposal: &mut Proposal, index: usize, now: i64) -> Result<()> {
+        record_approval(treasury, proposal, index, treasury.approvers[index], now)
+    }
+
+    #[test]
+    fn tracks_approvals_as_a_bitmask() {
+        let treasury = treasury(16, 16);
+        let mut proposal = proposal();
+
+        approve(&treasury, &mut proposal, 0, 0).unwrap();
+        approve(&treasury, &mut proposal, 15, 0).unwrap();
+        assert_eq!(proposal.approvals, 0b1000_0000_0000_0001);
+        assert_eq!(
+            approve(&treasury, &mut proposal, 15, 0).err(),
+            Some(TreasuryError::AlreadyApproved.into())
+        );
+        assert_eq!(treasury.approver_index(&treasury.approvers[15]).unwrap(), 15);
+        assert_eq!(
+            treasury.approver_index(&Pubkey::new_unique()).err(),
+            Some(TreasuryError::NotAnApprover.into())
+        );
+    }
+
+    #[test]
+    fn approves_at_threshold_and_starts_timelock() {
+        let treasury = treasury(3, 2);
+        let mut proposal = proposal();
+
+        approve(&treasury, &mut proposal, 1, 100).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Pending);
+        assert_eq!(proposal.executable_at, 0);
+
+        approve(&treasury, &mut proposal, 2, 200).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert_eq!(proposal.executable_at, 200 + Treasury::MIN_TIMELOCK_SECONDS);
+
+        // Late approvals can't restart the timelock
+        assert_eq!(
+            approve(&treasury, &mut proposal, 0, 300).err(),
+            Some(TreasuryError::ProposalNotOpen.into())
+        );
+    }
+
+    #[test]
+    fn executes_only_after_timelock() {
+        let treasury = treasury(2, 1);
+        let mut proposal = proposal();
+        assert_eq!(
+            proposal.check_executable(i64::MAX).err(),
+            Some(TreasuryError::NotApproved.into())
+        );
+
+        approve(&treasury, &mut proposal, 0, 1_000).unwrap();
+        let executable_at = 1_000 + Treasury::MIN_TIMELOCK_SECONDS;
+        assert_eq!(
+            proposal.check_executable(executable_at - 1).err(),
+            Some(TreasuryError::TimelockActive.into())
+        );
+        assert!(proposal.check_executable(executable_at).is_ok());
+    }
+
+    #[test]
+    fn cancels_open_proposals_only() {
+        let treasury = treasury(2, 1);
+
+        let mut pending = proposal();
+        pending.cancel().unwrap();
+        assert_eq!(pending.status, ProposalStatus::Cancelled);
+        assert_eq!(pending.cancel().err(), Some(TreasuryError::ProposalNotOpen.into()));
+        assert_eq!(
+            approve(&treasury, &mut pending, 0, 0).err(),
+            Some(TreasuryError::ProposalNotOpen.into())
+        );
+
+        let mut approved = proposal();
+        approve(&treasury, &mut approved, 0, 0).unwrap();
+        approved.cancel().unwrap();
+        assert_eq!(
+            approved.check_executable(i64::MAX).err(),
+            Some(TreasuryError::NotApproved.into())
+        );
+
+        let mut executed = proposal();
+        executed.status = ProposalStatus::Executed;
+        assert_eq!(executed.cancel().err(), Some(TreasuryError::ProposalNotOpen.into()));
+    }
+}
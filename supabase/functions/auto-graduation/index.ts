@@ -180,7 +180,7 @@ async function processTokenGraduation(
   // 3. Burn or distribute LP tokens
   // 4. Close bonding curve
 
-  const finalSolReserves = token.sol_raised || 326; // 326 SOL at graduation
+  const finalSolReserves = token.sol_raised || 85; // 85 SOL raised at graduation
   const remainingTokens = 1000000000 - (token.tokens_sold || 0); // Remaining supply
 
   // Simulate Raydium pool creation
@@ -27,7 +27,7 @@ const corsHeaders = {
 const BONDING_CURVE_CONFIG = {
   VIRTUAL_SOL_RESERVES: 30,
   VIRTUAL_TOKEN_RESERVES: 1073000000,
-  GRADUATION_THRESHOLD: 85000,
+  GRADUATION_THRESHOLD: 85, // Real SOL raised, matches the on-chain Standard preset
 };
 
 /**